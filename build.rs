@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+// `DEFAULT_CONFIG_PATH` picks the config embedded as `DEFAULT_CONFIG`,
+// relative paths are from the crate root
+fn main() {
+    println!("cargo:rerun-if-env-changed=DEFAULT_CONFIG_PATH");
+
+    let root = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let path = match std::env::var_os("DEFAULT_CONFIG_PATH") {
+        Some(path) => root.join(path),
+        None => root.join("src/default.json"),
+    };
+    if !path.is_file() {
+        panic!("DEFAULT_CONFIG_PATH '{}' isn't a file", path.display());
+    }
+
+    println!("cargo:rerun-if-changed={}", path.display());
+    println!(
+        "cargo:rustc-env=XML_TO_CSV_DEFAULT_CONFIG={}",
+        path.display()
+    );
+}
@@ -0,0 +1,273 @@
+/// An arithmetic expression over numbers and column titles.
+///
+/// Supports `+`, `-`, `*`, `/`, unary minus and parentheses with the usual precedence.
+/// a title that is a plain identifier (letters, digits and `_`, not starting with a digit)
+/// can be written as is, any other title is written inside braces, e.g. `{unit price}`
+#[derive(Debug)]
+pub enum Expr {
+    Number(f64),
+    Column(String),
+    Neg(Box<Expr>),
+    Binary {
+        op: Op,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut parser = Parser { s, pos: 0 };
+        let expr = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.pos != s.len() {
+            return Err(format!(
+                "Unexpected '{}' in expression {s:?}",
+                &s[parser.pos..]
+            ));
+        }
+        Ok(expr)
+    }
+
+    /// Every column title referenced by the expression, in order of appearance
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        self.collect_columns(&mut columns);
+        columns
+    }
+
+    fn collect_columns<'a>(&'a self, columns: &mut Vec<&'a str>) {
+        match self {
+            Expr::Number(_) => {}
+            Expr::Column(title) => columns.push(title),
+            Expr::Neg(expr) => expr.collect_columns(columns),
+            Expr::Binary { lhs, rhs, .. } => {
+                lhs.collect_columns(columns);
+                rhs.collect_columns(columns);
+            }
+        }
+    }
+
+    /// Evaluates the expression, `value` gives the value of a column by title.
+    /// values are trimmed before being parsed, a missing, empty or non numeric
+    /// value and dividing by zero are errors
+    pub fn eval<'a>(&self, value: &impl Fn(&str) -> Option<&'a str>) -> Result<f64, String> {
+        Ok(match self {
+            Expr::Number(number) => *number,
+            Expr::Column(title) => {
+                let raw = value(title).ok_or_else(|| format!("No column titled '{title}'"))?;
+                raw.trim()
+                    .parse()
+                    .map_err(|_| format!("Value of '{title}' isn't a number: {raw:?}"))?
+            }
+            Expr::Neg(expr) => -expr.eval(value)?,
+            Expr::Binary { op, lhs, rhs } => {
+                let (lhs, rhs) = (lhs.eval(value)?, rhs.eval(value)?);
+                match op {
+                    Op::Add => lhs + rhs,
+                    Op::Sub => lhs - rhs,
+                    Op::Mul => lhs * rhs,
+                    Op::Div if rhs == 0.0 => Err("Division by zero")?,
+                    Op::Div => lhs / rhs,
+                }
+            }
+        })
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.s[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// `term (('+' | '-') term)*`
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                Op::Add
+            } else if self.eat('-') {
+                Op::Sub
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.term()?;
+            lhs = Expr::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+    }
+
+    /// `unary (('*' | '/') unary)*`
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                Op::Mul
+            } else if self.eat('/') {
+                Op::Div
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.unary()?;
+            lhs = Expr::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+    }
+
+    /// `'-' unary | number | title | '{' title '}' | '(' expr ')'`
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat('(') {
+            let expr = self.expr()?;
+            if !self.eat(')') {
+                return Err(format!("Missing ')' in expression {:?}", self.s));
+            }
+            return Ok(expr);
+        }
+        let rest = &self.s[self.pos..];
+        if self.eat('{') {
+            let (title, _) = rest[1..]
+                .split_once('}')
+                .ok_or_else(|| format!("Missing '}}' in expression {:?}", self.s))?;
+            self.pos += title.len() + 1;
+            return Ok(Expr::Column(title.to_owned()));
+        }
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        let token = &rest[..len];
+        match token.chars().next() {
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let number = token
+                    .parse()
+                    .map_err(|_| format!("Invalid number '{token}' in expression {:?}", self.s))?;
+                self.pos += len;
+                Ok(Expr::Number(number))
+            }
+            Some(_) if !token.contains('.') => {
+                self.pos += len;
+                Ok(Expr::Column(token.to_owned()))
+            }
+            _ => Err(format!(
+                "Expected a number, title or '(' at '{rest}' in expression {:?}",
+                self.s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evaluates `expr` with the given column values
+    fn eval(expr: &str, values: &[(&str, &'static str)]) -> Result<f64, String> {
+        Expr::parse(expr)?.eval(&|title| {
+            values
+                .iter()
+                .find(|(t, _)| *t == title)
+                .map(|(_, value)| *value)
+        })
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1+2*3", &[]), Ok(7.0));
+        assert_eq!(eval("8 / 4 / 2", &[]), Ok(1.0));
+        assert_eq!(eval("1 - 2 + 3", &[]), Ok(2.0));
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(eval("(1+2)*3", &[]), Ok(9.0));
+        assert_eq!(eval("((2))", &[]), Ok(2.0));
+        assert!(eval("(1+2", &[]).unwrap_err().starts_with("Missing ')'"));
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("a - -b", &[("a", "1"), ("b", "2")]), Ok(3.0));
+        assert_eq!(eval("-(1+2)*2", &[]), Ok(-6.0));
+    }
+
+    #[test]
+    fn titles_in_braces() {
+        let values = [("unit price", " 2.5 "), ("qty", "4")];
+        assert_eq!(eval("qty * {unit price}", &values), Ok(10.0));
+        assert_eq!(
+            Expr::parse("qty * {unit price}").unwrap().columns(),
+            ["qty", "unit price"]
+        );
+        assert!(eval("{unit price", &[])
+            .unwrap_err()
+            .starts_with("Missing '}'"));
+    }
+
+    #[test]
+    fn trailing_garbage() {
+        assert_eq!(
+            eval("2 3", &[]),
+            Err("Unexpected '3' in expression \"2 3\"".to_owned())
+        );
+        assert!(eval("2 +", &[])
+            .unwrap_err()
+            .starts_with("Expected a number"));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(
+            eval("1 / (a - a)", &[("a", "3")]),
+            Err("Division by zero".to_owned())
+        );
+    }
+
+    #[test]
+    fn missing_and_non_numeric_columns() {
+        assert_eq!(eval("a + 1", &[]), Err("No column titled 'a'".to_owned()));
+        assert_eq!(
+            eval("a + 1", &[("a", "one")]),
+            Err("Value of 'a' isn't a number: \"one\"".to_owned())
+        );
+        assert_eq!(
+            eval("a + 1", &[("a", "")]),
+            Err("Value of 'a' isn't a number: \"\"".to_owned())
+        );
+    }
+}
@@ -1,32 +1,440 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, marker::PhantomData};
 
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{value::SeqAccessDeserializer, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 
-use crate::xml_path::PathType;
+use crate::{
+    compute::Expr,
+    xml_path::{Namespaces, Path, PathType},
+};
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(transparent)]
+/// The newest config version this build understands
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Either `{"version": 1, "namespaces": {...}, "csv_columns": [...]}` (with optional
+/// `record_path` and `row_filter`) or just the bare array of columns.
+/// configs without a version are version 1
+#[derive(Serialize, Debug)]
 pub struct Config<'l> {
-    #[serde(borrow = "'l")]
+    pub version: u32,
+    pub namespaces: Namespaces<'l>,
+    /// Makes every element this path selects a row of its own (see
+    /// [`crate::extract::find_records`]). paths of the columns and row filter then
+    /// start below the record element, the same way they normally start below the root
+    pub record_path: Option<Path<'l>>,
+    pub row_filter: Option<RowFilter<'l>>,
     pub csv_columns: Vec<CsvColumn<'l>>,
 }
 
+/// Picks the form by whether the config is an array or a map rather than with
+/// `#[serde(untagged)]`, which would report errors inside it without their location
+impl<'de: 'l, 'l> Deserialize<'de> for Config<'l> {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        d.deserialize_any(ConfigVisitor(PhantomData))
+    }
+}
+
+struct ConfigVisitor<'l>(PhantomData<Config<'l>>);
+impl<'de: 'l, 'l> Visitor<'de> for ConfigVisitor<'l> {
+    type Value = Config<'l>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("An array of columns or a map with `csv_columns`")
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        Ok(Config {
+            version: first_version(),
+            namespaces: Namespaces::new(),
+            record_path: None,
+            row_filter: None,
+            csv_columns: Vec::deserialize(SeqAccessDeserializer::new(seq))?,
+        })
+    }
+
+    /// The version is checked as soon as it's read so a newer config gets the upgrade
+    /// message rather than an error about syntax this version doesn't know. columns
+    /// written before the version are held until it is known, losing the location of
+    /// errors in them
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        use serde::de::{Error, IgnoredAny};
+
+        let mut version = None;
+        let mut namespaces = None;
+        let mut record_path = None;
+        let mut row_filter = None;
+        let mut csv_columns = None;
+        let mut held_columns = None;
+        while let Some(key) = map.next_key::<Cow<'_, str>>()? {
+            match key.as_ref() {
+                "version" => {
+                    let value = map.next_value()?;
+                    Config::check_version(value).map_err(A::Error::custom)?;
+                    version = Some(value);
+                }
+                "namespaces" => namespaces = Some(map.next_value()?),
+                "record_path" => record_path = map.next_value()?,
+                "row_filter" => row_filter = map.next_value()?,
+                "csv_columns" if version.is_some() => csv_columns = Some(map.next_value()?),
+                "csv_columns" => held_columns = Some(map.next_value::<serde_json::Value>()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        let csv_columns = match (csv_columns, held_columns) {
+            (Some(csv_columns), _) => csv_columns,
+            (None, Some(held)) => Vec::deserialize(held).map_err(A::Error::custom)?,
+            (None, None) => Err(A::Error::missing_field("csv_columns"))?,
+        };
+        Ok(Config {
+            version: version.unwrap_or_else(first_version),
+            namespaces: namespaces.unwrap_or_default(),
+            record_path,
+            row_filter,
+            csv_columns,
+        })
+    }
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+impl<'l> Config<'l> {
+    fn check_version(version: u32) -> Result<(), String> {
+        if version > CONFIG_VERSION {
+            Err(format!(
+                "Config version {version} is newer than the supported version {CONFIG_VERSION}, upgrade xml_to_csv to use this config"
+            ))?
+        }
+        Ok(())
+    }
+
+    /// Checks the version and every path in the config, naming the column of
+    /// the first invalid one.
+    /// also checks that computed columns only reference existing fields and don't
+    /// depend on themselves
+    pub fn validate(&self) -> Result<(), String> {
+        Config::check_version(self.version)?;
+        if let Some(record_path) = &self.record_path {
+            record_path
+                .validate_records()
+                .map_err(|e| format!("Invalid record path: {e}"))?;
+        }
+        if let Some(row_filter) = &self.row_filter {
+            row_filter
+                .path
+                .validate()
+                .map_err(|e| format!("Invalid row filter: {e}"))?;
+        }
+        for column in &self.csv_columns {
+            self.validate_column(&column.column_type, false)
+                .map_err(|e| format!("Invalid column '{}': {e}", column.title()))?;
+        }
+        self.compute_order()?;
+        Ok(())
+    }
+
+    fn validate_column(&self, column_type: &ColumnType<'_>, nested: bool) -> Result<(), String> {
+        match column_type {
+            ColumnType::Split { .. } if nested => {
+                Err("Cannot split a value inside `then`, `else` or `default`")?
+            }
+            ColumnType::ExpandColumns { .. } if nested => {
+                Err("Cannot expand a value inside `then`, `else` or `default`")?
+            }
+            ColumnType::Split { path, .. } | ColumnType::ExpandColumns { path, .. } => {
+                path.validate()?
+            }
+            ColumnType::ExtractXmlPath {
+                path,
+                default,
+                regex,
+                transforms,
+                ..
+            } => {
+                path.validate()?;
+                let captures = transforms.iter().filter_map(|transform| match transform {
+                    Transform::Regex(capture) => Some(capture),
+                    _ => None,
+                });
+                for RegexCapture { pattern, group, .. } in regex.iter().chain(captures) {
+                    if let Some(group) = group.filter(|g| *g >= pattern.0.captures_len()) {
+                        Err(format!("Regex '{}' has no group {group}", pattern.0))?
+                    }
+                }
+                if let Some(Branch::Column(column_type)) = default.as_deref() {
+                    // a fallback is needed before computed columns have their values
+                    if column_type.is_computed() {
+                        Err("Cannot use a computed column as a `default`")?
+                    }
+                    self.validate_column(column_type, true)?;
+                }
+            }
+            ColumnType::When {
+                then, otherwise, ..
+            } => {
+                for branch in [Some(then), otherwise.as_ref()].into_iter().flatten() {
+                    if let Branch::Column(column_type) = branch.as_ref() {
+                        self.validate_column(column_type, true)?;
+                    }
+                }
+            }
+            ColumnType::Compute { .. } | ColumnType::Text { .. } | ColumnType::Intrinsic { .. } => {
+            }
+        }
+        for title in column_type.references()? {
+            if self.field_column(&title).is_none() {
+                Err(format!("No column titled '{title}'"))?
+            }
+        }
+        Ok(())
+    }
+
+    /// Index of the column writing the field titled `title`, the first one if several do
+    pub fn field_column(&self, title: &str) -> Option<usize> {
+        self.csv_columns
+            .iter()
+            .position(|column| column.field_titles().iter().any(|t| t == title))
+    }
+
+    /// Indices of the computed columns (see [`ColumnType::is_computed`]) ordered
+    /// so every one comes after the computed columns it references
+    pub fn compute_order(&self) -> Result<Vec<usize>, String> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            Visiting,
+            Done,
+        }
+
+        fn visit(
+            config: &Config<'_>,
+            index: usize,
+            states: &mut [State],
+            stack: &mut Vec<usize>,
+            order: &mut Vec<usize>,
+        ) -> Result<(), String> {
+            match states[index] {
+                State::Done => return Ok(()),
+                State::Visiting => {
+                    let start = stack.iter().position(|i| *i == index).unwrap_or(0);
+                    let cycle: Vec<_> = stack[start..]
+                        .iter()
+                        .chain([&index])
+                        .map(|i| format!("'{}'", config.csv_columns[*i].title()))
+                        .collect();
+                    Err(format!("Cyclic column references: {}", cycle.join(" -> ")))?
+                }
+                State::Unvisited => {}
+            }
+            states[index] = State::Visiting;
+            stack.push(index);
+            let column = &config.csv_columns[index];
+            for title in column.column_type.references()? {
+                if let Some(dependency) = config.field_column(&title) {
+                    if config.csv_columns[dependency].column_type.is_computed() {
+                        visit(config, dependency, states, stack, order)?;
+                    }
+                }
+            }
+            stack.pop();
+            states[index] = State::Done;
+            order.push(index);
+            Ok(())
+        }
+
+        let mut states = vec![State::Unvisited; self.csv_columns.len()];
+        let mut order = Vec::new();
+        for (index, column) in self.csv_columns.iter().enumerate() {
+            if column.column_type.is_computed() {
+                visit(self, index, &mut states, &mut Vec::new(), &mut order)?;
+            }
+        }
+        Ok(order)
+    }
+
+    /// Every field title used more than once, in the order they are first repeated
+    pub fn duplicate_titles(&self) -> Vec<Cow<'_, str>> {
+        let titles = self.field_titles();
+        let mut duplicates: Vec<Cow<'_, str>> = Vec::new();
+        for (index, title) in titles.iter().enumerate() {
+            if titles[..index].contains(title) && !duplicates.contains(title) {
+                duplicates.push(title.clone());
+            }
+        }
+        duplicates
+    }
+
+    /// The title of every field written per row, in order, see [`CsvColumn::field_titles`]
+    pub fn field_titles(&self) -> Vec<Cow<'_, str>> {
+        self.csv_columns
+            .iter()
+            .flat_map(CsvColumn::field_titles)
+            .collect()
+    }
+}
+
+/// Only rows whose value at `path` meets the condition are written
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RowFilter<'l> {
+    #[serde(flatten)]
+    #[serde(borrow = "'l")]
+    pub path: PathType<'l>,
+    #[serde(flatten)]
+    pub options: ExtractOptions<'l>,
+    #[serde(flatten)]
+    pub condition: Condition<'l>,
+    /// what to do when the value can't be extracted, defaults to `abort`
+    #[serde(default)]
+    pub on_error: FilterErrorAction,
+}
+
+/// Written as `"operator": "equals", "value": "..."`, `non_empty` takes no value
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "operator", content = "value", rename_all = "snake_case")]
+pub enum Condition<'l> {
+    #[serde(borrow = "'l")]
+    Equals(Cow<'l, str>),
+    #[serde(borrow = "'l")]
+    Contains(Cow<'l, str>),
+    #[serde(borrow = "'l")]
+    Regex(Cow<'l, str>),
+    NonEmpty,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterErrorAction {
+    /// leave the row out
+    Skip,
+    /// stop with an error
+    #[default]
+    Abort,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CsvColumn<'l> {
+    /// defaults to what the column reads, see [`CsvColumn::title`]
     #[serde(borrow = "'l")]
-    pub title: Cow<'l, str>,
+    #[serde(default)]
+    pub title: Option<Cow<'l, str>>,
     #[serde(flatten)]
     pub column_type: ColumnType<'l>,
 }
 
+impl CsvColumn<'_> {
+    /// The title, or if it has none the path, text, intrinsic or expression it reads
+    /// (`when_{column}` for [`ColumnType::When`])
+    pub fn title(&self) -> Cow<'_, str> {
+        if let Some(title) = &self.title {
+            return Cow::Borrowed(title);
+        }
+        match &self.column_type {
+            ColumnType::Split { path, .. }
+            | ColumnType::ExpandColumns { path, .. }
+            | ColumnType::ExtractXmlPath { path, .. } => Cow::Owned(path.path().to_string()),
+            ColumnType::Text { text } => Cow::Borrowed(text),
+            ColumnType::Intrinsic { intrinsic } => Cow::Owned(format!("{intrinsic:?}")),
+            ColumnType::Compute { expr } => Cow::Borrowed(expr),
+            ColumnType::When { condition, .. } => Cow::Owned(format!("when_{}", condition.column)),
+        }
+    }
+
+    /// The titles of the fields the column writes. [`ColumnType::Split`] and
+    /// [`ColumnType::ExpandColumns`] write several, numbered from `_0`
+    pub fn field_titles(&self) -> Vec<Cow<'_, str>> {
+        let (prefix, count) = match &self.column_type {
+            ColumnType::Split { into, .. } => (self.title(), *into),
+            ColumnType::ExpandColumns {
+                max, title_prefix, ..
+            } => match title_prefix {
+                Some(prefix) => (Cow::Borrowed(prefix.as_ref()), *max),
+                None => (self.title(), *max),
+            },
+            _ => return vec![self.title()],
+        };
+        (0..count)
+            .map(|i| Cow::Owned(format!("{prefix}_{i}")))
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum ColumnType<'l> {
+    /// Extracts a value once and splits it on `delimiter` into `into` fields,
+    /// padding with empty fields or dropping extra parts.
+    /// the fields are titled `{title}_0` to `{title}_{into - 1}`
+    Split {
+        #[serde(flatten)]
+        #[serde(borrow = "'l")]
+        path: PathType<'l>,
+        #[serde(flatten)]
+        options: ExtractOptions<'l>,
+        delimiter: Cow<'l, str>,
+        into: usize,
+    },
+    /// Extracts a value from every element `path` matches, writing them into `max` fields
+    /// titled `{title_prefix}_0` to `{title_prefix}_{max - 1}` (the column's title
+    /// if no prefix is given) and padding with empty fields. like `record_path` a last
+    /// element name or `tag[child/text=value]` matches every such child of the element
+    /// the parts before it lead to. matches past `max` are dropped, with a warning
+    /// (see [`crate::extract::Record::Warning`]) if `warn_truncated` is set
+    ExpandColumns {
+        #[serde(flatten)]
+        #[serde(borrow = "'l")]
+        path: PathType<'l>,
+        #[serde(flatten)]
+        options: ExtractOptions<'l>,
+        max: usize,
+        #[serde(borrow = "'l")]
+        #[serde(default)]
+        title_prefix: Option<Cow<'l, str>>,
+        #[serde(default)]
+        warn_truncated: bool,
+    },
+    /// Extracts a single value. the extracted value is decoded first (if `decode` is set),
+    /// then trimmed, then has its whitespace collapsed, then is replaced by a `regex`
+    /// capture, then is reformatted and finally goes through `transforms` in order.
+    /// `transforms` can do all of these steps in any order, the separate fields
+    /// are kept for older configs.
+    /// `default` is used when extraction, decoding or reformatting fails, either
+    /// as is when it's a string or as the value of another (non computed) column type
     ExtractXmlPath {
         #[serde(flatten)]
         #[serde(borrow = "'l")]
         path: PathType<'l>,
-        default: Option<Cow<'l, str>>,
+        #[serde(flatten)]
+        options: ExtractOptions<'l>,
+        default: Option<Box<Branch<'l>>>,
+        decode: Option<Encoding>,
+        /// replace invalid UTF-8 in decoded values instead of failing
+        #[serde(default)]
+        decode_lossy: bool,
+        /// remove leading and trailing whitespace
+        #[serde(default)]
+        trim: bool,
+        /// replace every run of whitespace with a single space
+        #[serde(default)]
+        collapse_whitespace: bool,
+        regex: Option<RegexCapture>,
+        reformat: Option<Reformat<'l>>,
+        #[serde(borrow = "'l")]
+        #[serde(default)]
+        transforms: Vec<Transform<'l>>,
     },
     Text {
         text: Cow<'l, str>,
@@ -34,9 +442,221 @@ pub enum ColumnType<'l> {
     Intrinsic {
         intrinsic: Intrinsic,
     },
+    /// Arithmetic over the numeric values of other fields, referenced by title,
+    /// e.g. `qty * {unit price}`. see [`Expr`] for the syntax.
+    /// a referenced value that is empty or isn't a number, or dividing by zero,
+    /// is an error for the file
+    Compute {
+        #[serde(borrow = "'l")]
+        expr: Cow<'l, str>,
+    },
+    /// `then` if the field titled `condition.column` meets the condition, otherwise
+    /// `else` (empty if not given)
+    When {
+        #[serde(borrow = "'l")]
+        condition: FieldCondition<'l>,
+        #[serde(borrow = "'l")]
+        then: Box<Branch<'l>>,
+        #[serde(borrow = "'l")]
+        #[serde(rename = "else")]
+        otherwise: Option<Box<Branch<'l>>>,
+    },
+}
+
+impl ColumnType<'_> {
+    /// Computed columns get their value from other fields of the row,
+    /// so they are evaluated after all other columns
+    pub fn is_computed(&self) -> bool {
+        matches!(self, ColumnType::Compute { .. } | ColumnType::When { .. })
+    }
+
+    /// Titles of the fields this column's value is computed from
+    pub fn references(&self) -> Result<Vec<String>, String> {
+        Ok(match self {
+            ColumnType::Compute { expr } => Expr::parse(expr)?
+                .columns()
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+            ColumnType::When {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let mut references = vec![condition.column.to_string()];
+                for branch in [Some(then), otherwise.as_ref()].into_iter().flatten() {
+                    if let Branch::Column(column_type) = branch.as_ref() {
+                        references.extend(column_type.references()?);
+                    }
+                }
+                references
+            }
+            _ => Vec::new(),
+        })
+    }
+}
+
+/// A [`Condition`] tested against the value of the field titled `column`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FieldCondition<'l> {
+    #[serde(borrow = "'l")]
+    pub column: Cow<'l, str>,
+    #[serde(flatten)]
+    pub condition: Condition<'l>,
+}
+
+/// The value of a [`ColumnType::When`] or a `default`, either a literal
+/// or any column type that writes a single field
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Branch<'l> {
+    #[serde(borrow = "'l")]
+    Literal(Cow<'l, str>),
+    #[serde(borrow = "'l")]
+    Column(Box<ColumnType<'l>>),
+}
+
+/// Options changing how some [`PathType`]s read their value
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ExtractOptions<'l> {
+    /// placed between the text nodes joined by `path_text_deep`, defaults to nothing
+    #[serde(borrow = "'l")]
+    pub joiner: Option<Cow<'l, str>>,
+    /// placed between an attribute's name and value by `path_all_attrs`, defaults to `=`
+    pub pair_separator: Option<Cow<'l, str>>,
+    /// placed between attributes by `path_all_attrs`, defaults to `;`
+    pub entry_separator: Option<Cow<'l, str>>,
+    /// indent the output of `path_xml` over multiple lines
+    #[serde(default)]
+    pub pretty: bool,
+    /// write the namespace declarations in scope on the element output by `path_xml`
+    #[serde(default)]
+    pub include_namespaces: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Intrinsic {
     FilePath,
+    /// Canonical absolute path of the file, falls back to the
+    /// [`Intrinsic::FilePath`] value if the path can't be canonicalized
+    AbsoluteFilePath,
+    /// Name of the folder containing the file. for files directly inside the
+    /// scanned folder this is the scanned folder's own name, and it is empty
+    /// when that name can't be determined (e.g. `.` or `/`)
+    ParentDirName,
+    /// A random UUID generated once per run, the same for every row
+    RunId,
+    /// When the run started as RFC 3339 in local time, the same for every row
+    RunTimestamp,
+    /// SHA-256 of the config as lowercase hex, the same for every row.
+    /// the config is hashed after parsing, written back as compact JSON with fields in
+    /// the order they are declared in xml_to_csv, defaults filled in and paths in their
+    /// canonical form, so whitespace, key order, leaving out defaults and the file
+    /// format (json, yaml or toml) don't change the hash
+    ConfigHash,
+}
+
+/// Replaces a value with a group captured by `pattern` from it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RegexCapture {
+    pub pattern: Pattern,
+    /// defaults to the first group, or the whole match if the pattern has no groups.
+    /// a group that didn't take part in the match is empty
+    pub group: Option<usize>,
+    #[serde(default)]
+    pub on_no_match: OnNoMatch,
+}
+
+/// What a [`RegexCapture`] does with a value the pattern doesn't match
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum OnNoMatch {
+    /// fail, so the column's `default` is used (or the file fails without one)
+    Default,
+    /// keep the value unchanged
+    #[default]
+    Passthrough,
+    /// replace the value with an empty string
+    Empty,
+}
+
+/// A regex compiled when the config is loaded
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern(pub regex::Regex);
+
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        regex::Regex::new(&s).map(Pattern)
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> Self {
+        pattern.0.as_str().to_owned()
+    }
+}
+
+/// A step of the `transforms` of [`ColumnType::ExtractXmlPath`], either `"trim"`,
+/// `"collapse_whitespace"` or an object with one key, e.g. `{"truncate": {"max": 10}}`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform<'l> {
+    /// remove leading and trailing whitespace
+    Trim,
+    /// replace every run of whitespace with a single space
+    CollapseWhitespace,
+    /// `lossy` replaces invalid UTF-8 in the decoded value instead of failing
+    Decode {
+        encoding: Encoding,
+        #[serde(default)]
+        lossy: bool,
+    },
+    Regex(RegexCapture),
+    Reformat(#[serde(borrow = "'l")] Reformat<'l>),
+    /// keep the first `max` chars (Unicode scalar values), replacing the last
+    /// one kept with `…` if `ellipsis` is set and anything was cut
+    Truncate {
+        max: usize,
+        #[serde(default)]
+        ellipsis: bool,
+    },
+}
+
+/// Parses a value as a date or number and writes it back out in a consistent format
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Reformat<'l> {
+    #[serde(flatten)]
+    #[serde(borrow = "'l")]
+    pub kind: ReformatKind<'l>,
+    /// pass values that can't be parsed through untouched instead of failing
+    #[serde(default)]
+    pub passthrough: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReformatKind<'l> {
+    /// `from` and `to` are chrono strftime formats. `from` may describe a date,
+    /// a date and time, or a date and time with an offset. `to` defaults to
+    /// ISO-8601 (`2023-01-31`, `2023-01-31T13:45:00` or RFC 3339 with an offset)
+    Date {
+        #[serde(borrow = "'l")]
+        from: Cow<'l, str>,
+        to: Option<Cow<'l, str>>,
+    },
+    /// Rounds to `decimals` places (if set) and groups the integer digits
+    /// in threes with `thousands` (if set)
+    Number {
+        decimals: Option<usize>,
+        thousands: Option<Cow<'l, str>>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Encoding {
+    Base64,
+    Hex,
 }
@@ -0,0 +1,1118 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    error::Error,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    compute,
+    config::{self, Config},
+    filter::Matcher,
+    input, transform, xml_path,
+};
+
+/// How files are read before anything is extracted from them
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReadOptions {
+    /// read every file with this encoding instead of detecting it
+    pub encoding: Option<&'static encoding_rs::Encoding>,
+    /// skip files that are empty or only contain whitespace
+    pub skip_empty: bool,
+    /// handle every top level element of a file as its own document
+    pub fragments: bool,
+    /// memory map files instead of reading them, falling back to reading
+    /// files that can't be mapped
+    pub mmap: bool,
+    /// how many more times to try reading a file after an I/O error, see [`Record::Retry`].
+    /// parse errors aren't retried
+    pub io_retries: u32,
+    /// wait before the first retry, doubled for every retry after it
+    pub io_retry_delay: Duration,
+}
+
+#[derive(Debug)]
+pub enum Record {
+    Row {
+        path: PathBuf,
+        values: Vec<String>,
+    },
+    Skipped {
+        path: PathBuf,
+        reason: SkipReason,
+    },
+    /// Reading the file failed with `error` and is tried again after the retry delay
+    /// once the next record is asked for, `attempt` counts from 1
+    Retry {
+        path: PathBuf,
+        attempt: u32,
+        error: String,
+    },
+    /// Something about a row that is written anyway, e.g. a truncated `expand_columns`.
+    /// comes before the row it is about
+    Warning {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SkipReason {
+    Empty,
+    Filtered,
+    NoRecords,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::Empty => write!(f, "skipping empty"),
+            SkipReason::Filtered => write!(f, "filtered out"),
+            SkipReason::NoRecords => write!(f, "no records"),
+        }
+    }
+}
+
+/// The element a row is read from and the root element of its document,
+/// which absolute paths are followed from
+#[derive(Debug, Clone, Copy)]
+pub struct Scope<'x> {
+    pub root: &'x xmltree::Element,
+    pub record: &'x xmltree::Element,
+}
+
+impl<'x> Scope<'x> {
+    /// Rows read from the root element itself
+    pub fn root(root: &'x xmltree::Element) -> Self {
+        Self { root, record: root }
+    }
+
+    /// The element `path` is followed from
+    pub fn start(&self, path: &xml_path::Path) -> &'x xmltree::Element {
+        if path.absolute {
+            self.root
+        } else {
+            self.record
+        }
+    }
+}
+
+/// Why a file, or one of its rows, couldn't be read
+#[derive(Debug)]
+pub struct RecordError {
+    pub path: PathBuf,
+    /// title of the column that failed, if only a single column did
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl RecordError {
+    fn new(path: &Path, column: Option<&str>, error: Box<dyn Error>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            column: column.map(str::to_owned),
+            message: error.to_string(),
+        }
+    }
+
+    /// The error as a JSON object with the file `path`, failed `column`
+    /// (null if the whole file failed) and `error` message
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "path": self.path.to_string_lossy(),
+            "column": self.column,
+            "error": self.message,
+        })
+    }
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for RecordError {}
+
+/// Values that are the same for every row of a run, see [`config::Intrinsic::RunId`]
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub id: String,
+    pub started: String,
+    /// see [`config::Intrinsic::ConfigHash`]
+    pub config_hash: String,
+}
+
+impl Run {
+    /// A new random id, started now
+    pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+        use sha2::{Digest, Sha256};
+
+        let canonical = serde_json::to_vec(config)
+            .map_err(|e| format!("Failed to serialize config to hash it: {e}"))?;
+        Ok(Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            started: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            config_hash: hex::encode(Sha256::digest(canonical)),
+        })
+    }
+}
+
+/// Reads and parses files one at a time as it is iterated, so only the rows
+/// of the current file are ever held in memory
+pub struct Records<'a, I> {
+    config: &'a Config<'a>,
+    paths: I,
+    options: ReadOptions,
+    columns: Columns<'a>,
+    run: Run,
+    /// reused for every file that isn't memory mapped
+    buffer: Vec<u8>,
+    row_filter: Option<(&'a config::RowFilter<'a>, Matcher<'a>)>,
+    pending: VecDeque<Result<Record, RecordError>>,
+    /// the file to read again before moving on to the next one
+    retry: Option<PendingRetry>,
+    stop: Option<&'a AtomicBool>,
+}
+
+struct PendingRetry {
+    path: PathBuf,
+    attempt: u32,
+    delay: Duration,
+}
+
+impl<'a, I: Iterator<Item = PathBuf>> Records<'a, I> {
+    /// Fails if the config doesn't pass [`Config::validate`]
+    pub fn new(
+        config: &'a Config<'a>,
+        paths: I,
+        options: ReadOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let row_filter = config
+            .row_filter
+            .as_ref()
+            .map(|row_filter| Matcher::new(&row_filter.condition).map(|m| (row_filter, m)))
+            .transpose()
+            .map_err(|e| format!("Failed to load row filter: {e}"))?;
+        Ok(Self {
+            config,
+            paths,
+            options,
+            columns: Columns::new(config)?,
+            run: Run::new(config)?,
+            buffer: Vec::new(),
+            row_filter,
+            pending: VecDeque::new(),
+            retry: None,
+            stop: None,
+        })
+    }
+
+    /// Ends the iteration early once `stop` is set, also cutting short the wait
+    /// before a retry
+    pub fn interrupt_on(mut self, stop: &'a AtomicBool) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+
+    /// Sleeps for `delay` unless stopped first, false if it was
+    fn wait(&self, delay: Duration) -> bool {
+        let end = Instant::now() + delay;
+        loop {
+            if self.stopped() {
+                return false;
+            }
+            let left = end.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return true;
+            }
+            std::thread::sleep(left.min(Duration::from_millis(50)));
+        }
+    }
+
+    /// Reads `path` and queues its records, `attempt` is 0 unless this is a retry
+    fn read_file(&mut self, path: &Path, attempt: u32) -> Result<(), Box<dyn Error>> {
+        let mapped = self.options.mmap.then(|| input::map_file(path)).flatten();
+        let xml_file: &[u8] = match &mapped {
+            Some(mapped) => mapped,
+            None => {
+                self.buffer.clear();
+                match std::fs::File::open(path)
+                    .and_then(|mut file| file.read_to_end(&mut self.buffer))
+                {
+                    Ok(_) => {}
+                    Err(e) if attempt < self.options.io_retries => {
+                        let attempt = attempt + 1;
+                        self.retry = Some(PendingRetry {
+                            path: path.to_path_buf(),
+                            attempt,
+                            delay: self
+                                .options
+                                .io_retry_delay
+                                .saturating_mul(2u32.saturating_pow(attempt - 1)),
+                        });
+                        self.pending.push_back(Ok(Record::Retry {
+                            path: path.to_path_buf(),
+                            attempt,
+                            error: e.to_string(),
+                        }));
+                        return Ok(());
+                    }
+                    Err(e) => Err(format!(
+                        "Failed to open xml file '{}': {e}",
+                        path.to_string_lossy()
+                    ))?,
+                }
+                &self.buffer
+            }
+        };
+
+        let xml_file = input::to_utf8(xml_file, self.options.encoding);
+
+        if self.options.skip_empty && xml_file.trim_ascii().is_empty() {
+            self.pending.push_back(Ok(Record::Skipped {
+                path: path.to_path_buf(),
+                reason: SkipReason::Empty,
+            }));
+            return Ok(());
+        }
+
+        let xml_file = if self.options.fragments {
+            Cow::Owned(input::wrap_fragments(&xml_file))
+        } else {
+            xml_file
+        };
+
+        let xml = input::parse_xml(&xml_file)
+            .map_err(|e| format!("Failed to parse xml file '{}': {e}", path.to_string_lossy()))?;
+
+        let mut roots: Vec<_> = if self.options.fragments {
+            xml.children
+                .iter()
+                .filter_map(|v| v.as_element())
+                .map(Scope::root)
+                .collect()
+        } else {
+            vec![Scope::root(&xml)]
+        };
+        if let Some(record_path) = &self.config.record_path {
+            let mut records = Vec::new();
+            for Scope { root, .. } in roots {
+                let found =
+                    find_records(root, record_path, &self.config.namespaces).map_err(|e| {
+                        format!(
+                            "Failed to find records in xml file '{}': {e}",
+                            path.to_string_lossy()
+                        )
+                    })?;
+                records.extend(found.into_iter().map(|record| Scope { root, record }));
+            }
+            if records.is_empty() {
+                self.pending.push_back(Ok(Record::Skipped {
+                    path: path.to_path_buf(),
+                    reason: SkipReason::NoRecords,
+                }));
+                return Ok(());
+            }
+            roots = records;
+        }
+
+        for xml in roots {
+            let mut warnings = Vec::new();
+            let values = match parse_row(path, xml, &self.columns, &self.run, &mut warnings) {
+                Ok(values) => values.into_iter().map(Cow::into_owned).collect(),
+                Err(e) => {
+                    self.pending.push_back(Err(e));
+                    continue;
+                }
+            };
+
+            if let Some((row_filter, matcher)) = &self.row_filter {
+                let keep = match extract_from_xml(
+                    xml,
+                    &row_filter.path,
+                    &row_filter.options,
+                    &self.config.namespaces,
+                ) {
+                    Ok(value) => matcher.matches(&value),
+                    Err(e) => match row_filter.on_error {
+                        config::FilterErrorAction::Skip => false,
+                        config::FilterErrorAction::Abort => {
+                            self.pending.push_back(Err(RecordError::new(
+                                path,
+                                None,
+                                format!(
+                                    "Failed to evaluate row filter for xml file '{}': {e}",
+                                    path.to_string_lossy()
+                                )
+                                .into(),
+                            )));
+                            continue;
+                        }
+                    },
+                };
+                if !keep {
+                    self.pending.push_back(Ok(Record::Skipped {
+                        path: path.to_path_buf(),
+                        reason: SkipReason::Filtered,
+                    }));
+                    continue;
+                }
+            }
+
+            self.pending.extend(warnings.into_iter().map(|message| {
+                Ok(Record::Warning {
+                    path: path.to_path_buf(),
+                    message,
+                })
+            }));
+            self.pending.push_back(Ok(Record::Row {
+                path: path.to_path_buf(),
+                values,
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl<I: Iterator<Item = PathBuf>> Iterator for Records<'_, I> {
+    type Item = Result<Record, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.stopped() {
+                return None;
+            }
+            if let Some(record) = self.pending.pop_front() {
+                return Some(record);
+            }
+            // a retry waits only once its `Record::Retry` has been returned
+            let (path, attempt) = match self.retry.take() {
+                Some(retry) => {
+                    if !self.wait(retry.delay) {
+                        return None;
+                    }
+                    (retry.path, retry.attempt)
+                }
+                None => (self.paths.next()?, 0),
+            };
+            if let Err(e) = self.read_file(&path, attempt) {
+                self.pending
+                    .push_back(Err(RecordError::new(&path, None, e)));
+            }
+        }
+    }
+}
+
+pub type RowResult = Result<Vec<String>, Box<dyn Error>>;
+
+/// The values of every row of every file in `paths`, read lazily (see [`Records`]).
+/// skipped files and rows are left out
+pub fn rows<'a>(
+    config: &'a Config<'a>,
+    paths: impl Iterator<Item = PathBuf> + 'a,
+) -> Result<impl Iterator<Item = RowResult> + 'a, Box<dyn Error>> {
+    let records = Records::new(config, paths, ReadOptions::default())?;
+    Ok(records.filter_map(|record| match record {
+        Ok(Record::Row { values, .. }) => Some(Ok(values)),
+        Ok(Record::Skipped { .. } | Record::Retry { .. } | Record::Warning { .. }) => None,
+        Err(e) => Some(Err(e.into())),
+    }))
+}
+
+/// Everything about the columns of a config that doesn't depend on the file
+/// (parsed expressions, compiled regexes, evaluation order), worked out once
+/// before any file is read
+pub struct Columns<'a> {
+    config: &'a Config<'a>,
+    resolved: Vec<Resolved<'a>>,
+    /// index of the first field of every column
+    fields: Vec<usize>,
+    /// computed columns, in the order they are evaluated in
+    order: Vec<usize>,
+    /// index of every field by title, the first one if several share a title
+    field_index: HashMap<Cow<'a, str>, usize>,
+}
+
+enum Resolved<'a> {
+    /// evaluated per file as written in the config
+    Column(&'a config::ColumnType<'a>),
+    Literal(&'a str),
+    Compute(compute::Expr),
+    When {
+        field: usize,
+        matcher: Matcher<'a>,
+        then: Box<Resolved<'a>>,
+        otherwise: Option<Box<Resolved<'a>>>,
+    },
+}
+
+impl<'a> Columns<'a> {
+    /// Fails if the config doesn't pass [`Config::validate`]
+    pub fn new(config: &'a Config<'a>) -> Result<Self, Box<dyn Error>> {
+        config.validate()?;
+        let mut field_index = HashMap::new();
+        for (index, title) in config.field_titles().into_iter().enumerate() {
+            field_index.entry(title).or_insert(index);
+        }
+
+        let mut columns = Self {
+            config,
+            resolved: Vec::with_capacity(config.csv_columns.len()),
+            fields: Vec::with_capacity(config.csv_columns.len()),
+            order: config.compute_order()?,
+            field_index,
+        };
+        let mut field = 0;
+        for column in &config.csv_columns {
+            columns.fields.push(field);
+            field += match column.column_type {
+                config::ColumnType::Split { into, .. } => into,
+                config::ColumnType::ExpandColumns { max, .. } => max,
+                _ => 1,
+            };
+            let resolved = columns
+                .resolve(&column.column_type)
+                .map_err(|e| format!("Invalid column '{}': {e}", column.title()))?;
+            columns.resolved.push(resolved);
+        }
+        Ok(columns)
+    }
+
+    fn resolve(
+        &self,
+        column_type: &'a config::ColumnType<'a>,
+    ) -> Result<Resolved<'a>, Box<dyn Error>> {
+        Ok(match column_type {
+            config::ColumnType::Compute { expr } => Resolved::Compute(compute::Expr::parse(expr)?),
+            config::ColumnType::When {
+                condition,
+                then,
+                otherwise,
+            } => Resolved::When {
+                field: *self
+                    .field_index
+                    .get(condition.column.as_ref())
+                    .ok_or_else(|| format!("No column titled '{}'", condition.column))?,
+                matcher: Matcher::new(&condition.condition)?,
+                then: Box::new(self.resolve_branch(then)?),
+                otherwise: otherwise
+                    .as_ref()
+                    .map(|branch| self.resolve_branch(branch).map(Box::new))
+                    .transpose()?,
+            },
+            column_type => Resolved::Column(column_type),
+        })
+    }
+
+    fn resolve_branch(
+        &self,
+        branch: &'a config::Branch<'a>,
+    ) -> Result<Resolved<'a>, Box<dyn Error>> {
+        match branch {
+            config::Branch::Literal(literal) => Ok(Resolved::Literal(literal)),
+            config::Branch::Column(column_type) => self.resolve(column_type),
+        }
+    }
+}
+
+/// The values of a row, anything worth a warning about them is added to `warnings`
+pub fn parse_row<'l>(
+    file: &Path,
+    xml: Scope<'l>,
+    columns: &'l Columns<'_>,
+    run: &'l Run,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Cow<'l, str>>, RecordError> {
+    let config = columns.config;
+    let mut vals = Vec::with_capacity(columns.field_index.len());
+    for (column, resolved) in config.csv_columns.iter().zip(&columns.resolved) {
+        match resolved {
+            Resolved::Column(config::ColumnType::Split {
+                path,
+                options,
+                delimiter,
+                into,
+            }) => {
+                let res =
+                    extract_from_xml(xml, path, options, &config.namespaces).map_err(|e| {
+                        let message = format!(
+                            "Failed to extract column from xml file '{}': {e}",
+                            file.to_string_lossy()
+                        );
+                        RecordError::new(file, Some(&column.title()), message.into())
+                    })?;
+                let mut parts = res.split(delimiter.as_ref());
+                vals.extend((0..*into).map(|_| Cow::Owned(parts.next().unwrap_or("").to_owned())));
+            }
+            Resolved::Column(config::ColumnType::ExpandColumns {
+                path,
+                options,
+                max,
+                warn_truncated,
+                ..
+            }) => {
+                let values =
+                    expand_from_xml(xml, path, options, &config.namespaces).map_err(|e| {
+                        let message = format!(
+                            "Failed to extract column from xml file '{}': {e}",
+                            file.to_string_lossy()
+                        );
+                        RecordError::new(file, Some(&column.title()), message.into())
+                    })?;
+                if *warn_truncated && values.len() > *max {
+                    warnings.push(format!(
+                        "column '{}' matched {} elements in xml file '{}', only the first {max} are written",
+                        column.title(),
+                        values.len(),
+                        file.to_string_lossy()
+                    ));
+                }
+                let mut values = values.into_iter();
+                vals.extend((0..*max).map(|_| Cow::Owned(values.next().unwrap_or_default())));
+            }
+            Resolved::Column(column_type) => vals.push(
+                column_value(file, xml, config, run, &column.title(), column_type)
+                    .map_err(|e| RecordError::new(file, Some(&column.title()), e))?,
+            ),
+            // filled in once the fields they reference have their values
+            _ => vals.push(Cow::Borrowed("")),
+        }
+    }
+
+    for &index in &columns.order {
+        let title = &config.csv_columns[index].title();
+        let value = computed_value(
+            file,
+            xml,
+            columns,
+            run,
+            title,
+            &columns.resolved[index],
+            &vals,
+        )
+        .map_err(|e| RecordError::new(file, Some(title), e))?;
+        vals[columns.fields[index]] = value;
+    }
+    Ok(vals)
+}
+
+/// The value of a computed column, `vals` holds the fields it references
+fn computed_value<'l>(
+    file: &Path,
+    xml: Scope<'l>,
+    columns: &'l Columns<'_>,
+    run: &'l Run,
+    title: &str,
+    resolved: &'l Resolved<'_>,
+    vals: &[Cow<'_, str>],
+) -> Result<Cow<'l, str>, Box<dyn Error>> {
+    Ok(match resolved {
+        Resolved::Column(column_type) => {
+            column_value(file, xml, columns.config, run, title, column_type)?
+        }
+        Resolved::Literal(literal) => Cow::Borrowed(literal),
+        Resolved::Compute(expr) => {
+            let field = |title: &str| columns.field_index.get(title).map(|i| &*vals[*i]);
+            let res = expr.eval(&field).map_err(|e| {
+                format!(
+                    "Failed to compute column '{title}' for xml file '{}': {e}",
+                    file.to_string_lossy()
+                )
+            })?;
+            Cow::Owned(res.to_string())
+        }
+        Resolved::When {
+            field,
+            matcher,
+            then,
+            otherwise,
+        } => {
+            let branch = if matcher.matches(&vals[*field]) {
+                Some(then)
+            } else {
+                otherwise.as_ref()
+            };
+            match branch {
+                Some(branch) => computed_value(file, xml, columns, run, title, branch, vals)?,
+                None => Cow::Borrowed(""),
+            }
+        }
+    })
+}
+
+fn column_value<'l>(
+    file: &Path,
+    xml: Scope<'l>,
+    config: &'l Config<'_>,
+    run: &'l Run,
+    title: &str,
+    column_type: &'l config::ColumnType<'_>,
+) -> Result<Cow<'l, str>, Box<dyn Error>> {
+    Ok(match column_type {
+        config::ColumnType::ExtractXmlPath {
+            path,
+            options,
+            default,
+            decode,
+            decode_lossy,
+            trim,
+            collapse_whitespace,
+            regex,
+            reformat,
+            transforms,
+        } => {
+            let res = extract_from_xml(xml, path, options, &config.namespaces)
+                .and_then(|res| match decode {
+                    Some(encoding) => transform::decode(&res, encoding, *decode_lossy),
+                    None => Ok(res),
+                })
+                .map(|res| if *trim { res.trim().to_owned() } else { res })
+                .map(|res| {
+                    if *collapse_whitespace {
+                        transform::collapse_whitespace(&res)
+                    } else {
+                        res
+                    }
+                })
+                .and_then(|res| match regex {
+                    Some(capture) => transform::regex_capture(res, capture),
+                    None => Ok(res),
+                })
+                .and_then(|res| match reformat {
+                    Some(reformat) => transform::reformat(res, reformat),
+                    None => Ok(res),
+                })
+                .and_then(|res| transforms.iter().try_fold(res, transform::apply));
+            if let Some(default) = default {
+                match (res, default.as_ref()) {
+                    (Ok(res), _) => Cow::Owned(res),
+                    (Err(_), config::Branch::Literal(default)) => Cow::Borrowed(default.as_ref()),
+                    (Err(_), config::Branch::Column(column_type)) => {
+                        column_value(file, xml, config, run, title, column_type)?
+                    }
+                }
+            } else {
+                Cow::Owned(res.map_err(|e| {
+                    format!(
+                        "Failed to extract column from xml file '{}': {e}",
+                        file.to_string_lossy()
+                    )
+                })?)
+            }
+        }
+        config::ColumnType::Split { .. } | config::ColumnType::ExpandColumns { .. } => {
+            Err(format!("Column '{title}' writes more than one field"))?
+        }
+        config::ColumnType::Compute { .. } | config::ColumnType::When { .. } => {
+            unreachable!("`Config::validate` rejects computed defaults, other computed columns are resolved by `Columns::new`")
+        }
+        config::ColumnType::Text { text } => Cow::Borrowed(text.as_ref()),
+        config::ColumnType::Intrinsic { intrinsic } => match intrinsic {
+            config::Intrinsic::FilePath => Cow::Owned(file.to_string_lossy().into_owned()),
+            config::Intrinsic::AbsoluteFilePath => Cow::Owned(
+                std::fs::canonicalize(file)
+                    .unwrap_or_else(|_| file.to_path_buf())
+                    .into_os_string()
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            config::Intrinsic::ParentDirName => Cow::Owned(
+                file.parent()
+                    .and_then(|parent| parent.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+            config::Intrinsic::RunId => Cow::Borrowed(run.id.as_str()),
+            config::Intrinsic::RunTimestamp => Cow::Borrowed(run.started.as_str()),
+            config::Intrinsic::ConfigHash => Cow::Borrowed(run.config_hash.as_str()),
+        },
+    })
+}
+
+pub fn extract_from_xml(
+    xml: Scope<'_>,
+    xml_path: &xml_path::PathType,
+    options: &config::ExtractOptions,
+    namespaces: &xml_path::Namespaces,
+) -> Result<String, Box<dyn Error>> {
+    let path = xml_path.path();
+    let element = find_element(xml.start(path), element_parts(xml_path), path, namespaces)?;
+    extract_from_element(element, xml_path, options, namespaces)
+}
+
+/// Extracts `xml_path` from every element its parts match, see [`config::ColumnType::ExpandColumns`]
+fn expand_from_xml(
+    xml: Scope<'_>,
+    xml_path: &xml_path::PathType,
+    options: &config::ExtractOptions,
+    namespaces: &xml_path::Namespaces,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let path = xml_path.path();
+    let xml = xml.start(path);
+    let elements = match element_parts(xml_path).split_last() {
+        Some((last, parents)) => find_all(xml, parents, last, path, namespaces)?,
+        None => vec![xml],
+    };
+    elements
+        .into_iter()
+        .map(|element| extract_from_element(element, xml_path, options, namespaces))
+        .collect()
+}
+
+/// The parts leading to the element a value is extracted from, all of them
+/// except for `path_attr` and `path_match_count` where the last part is looked up in it
+fn element_parts<'p>(xml_path: &'p xml_path::PathType) -> &'p [xml_path::PathPart<'p>] {
+    let parts = &xml_path.path().parts;
+    match xml_path {
+        xml_path::PathType::PathAttr(_) | xml_path::PathType::PathMatchCount(_) => {
+            parts.split_last().map_or(&[], |(_, parents)| parents)
+        }
+        _ => parts,
+    }
+}
+
+/// Extracts `xml_path` from `element`, the element its [`element_parts`] lead to
+fn extract_from_element(
+    element: &xmltree::Element,
+    xml_path: &xml_path::PathType,
+    options: &config::ExtractOptions,
+    namespaces: &xml_path::Namespaces,
+) -> Result<String, Box<dyn Error>> {
+    match &xml_path {
+        xml_path::PathType::PathText(path) => Ok(element_text(element)
+            .ok_or_else(|| format!("Failed to get text from {}", path))?
+            .into_owned()),
+        xml_path::PathType::PathTextDeep(path) => {
+            let mut texts = Vec::new();
+            collect_text(element, &mut texts);
+            if texts.is_empty() {
+                Err(format!("Failed to get text from {}", path))?
+            }
+            Ok(texts.join(options.joiner.as_deref().unwrap_or("")))
+        }
+        xml_path::PathType::PathAllAttrs(_) => {
+            let pair = options.pair_separator.as_deref().unwrap_or("=");
+            let entry = options.entry_separator.as_deref().unwrap_or(";");
+            let mut attributes: Vec<_> = element.attributes.iter().collect();
+            attributes.sort();
+            let attributes: Vec<_> = attributes
+                .into_iter()
+                .map(|(name, value)| {
+                    format!(
+                        "{}{pair}{}",
+                        transform::escape(name, &[pair, entry]),
+                        transform::escape(value, &[pair, entry])
+                    )
+                })
+                .collect();
+            Ok(attributes.join(entry))
+        }
+        xml_path::PathType::PathLen(_) => Ok(element.children.len().to_string()),
+        xml_path::PathType::PathMatchCount(path) => {
+            let last = path.parts.last().ok_or("Paths need at least one part")?;
+            let children = element.children.iter().filter_map(|v| v.as_element());
+            let count = match last {
+                xml_path::PathPart::Element(name) => {
+                    children.filter(|v| name.matches(v, namespaces)).count()
+                }
+                xml_path::PathPart::ChildTextMatch { tag, child, value } => children
+                    .filter(|v| child_text_matches(v, tag, child, value, namespaces))
+                    .count(),
+                _ => usize::from(
+                    find_element(element, std::slice::from_ref(last), path, namespaces).is_ok(),
+                ),
+            };
+            Ok(count.to_string())
+        }
+        xml_path::PathType::PathComment(path) => Ok(element
+            .children
+            .iter()
+            .find_map(|v| match v {
+                xmltree::XMLNode::Comment(comment) => Some(comment.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| format!("Failed to get comment from {}", path))?),
+        xml_path::PathType::PathPI { path, target } => Ok(element
+            .children
+            .iter()
+            .find_map(|v| match v {
+                xmltree::XMLNode::ProcessingInstruction(name, data) if name == target => {
+                    Some(data.clone().unwrap_or_default())
+                }
+                _ => None,
+            })
+            .ok_or_else(|| {
+                format!("Failed to get processing instruction '{target}' from {path}")
+            })?),
+        xml_path::PathType::PathXml(_) => {
+            let mut element = Cow::Borrowed(element);
+            if !options.include_namespaces {
+                strip_namespaces(element.to_mut());
+            }
+            let config = xmltree::EmitterConfig::new()
+                .perform_indent(options.pretty)
+                .write_document_declaration(false);
+            let mut xml = Vec::new();
+            element
+                .write_with_config(&mut xml, config)
+                .map_err(|e| format!("Failed to write xml: {e}"))?;
+            Ok(String::from_utf8(xml)?)
+        }
+        xml_path::PathType::PathAttr(path) => {
+            let last = path.parts.last().ok_or("Paths need at least one part")?;
+            match last {
+                xml_path::PathPart::Element(name) | xml_path::PathPart::Attribute(name) => {
+                    Ok(find_attribute(element, name, namespaces)
+                        .ok_or_else(|| format!("Failed to get attribute from path: {}", path))?
+                        .to_owned())
+                }
+                // xmltree's attribute map has no stable order so they're sorted by name
+                xml_path::PathPart::Index(index) => {
+                    let mut attributes: Vec<_> = element.attributes.iter().collect();
+                    attributes.sort();
+                    Ok(attributes
+                        .get(*index)
+                        .ok_or_else(|| {
+                            format!(
+                                "Cannot get attribute {index}, only {} exist from path: {path}",
+                                attributes.len()
+                            )
+                        })?
+                        .1
+                        .to_owned())
+                }
+                xml_path::PathPart::NthOfType { .. }
+                | xml_path::PathPart::ChildTextMatch { .. } => {
+                    Err("Cannot use `[...]` for attributes")?
+                }
+            }
+        }
+    }
+}
+
+/// Follows `parts` (of `path`, used in errors) down from `xml`
+fn find_element<'x>(
+    xml: &'x xmltree::Element,
+    parts: &[xml_path::PathPart],
+    path: &xml_path::Path,
+    namespaces: &xml_path::Namespaces,
+) -> Result<&'x xmltree::Element, Box<dyn Error>> {
+    let mut element = xml;
+    for part in parts {
+        match part {
+            xml_path::PathPart::Element(node_name) => {
+                element = element
+                    .children
+                    .iter()
+                    .filter_map(|v| v.as_element())
+                    .find(|v| node_name.matches(v, namespaces))
+                    .ok_or_else(|| {
+                        format!("Cannot find node: {} from xml path: {}", node_name, path)
+                    })?;
+            }
+            xml_path::PathPart::Attribute(_) => {
+                Err(format!("Cannot follow an attribute from xml path: {path}"))?
+            }
+            xml_path::PathPart::Index(index) => {
+                element = element
+                    .children
+                    .get(*index)
+                    .ok_or_else(|| format!("Cannog get child node: {index}"))
+                    .map(|v| {
+                        v.as_element().ok_or_else(|| {
+                            format!("The item at the index: {index} is not an element")
+                        })
+                    })??;
+            }
+            xml_path::PathPart::NthOfType { tag, n } => {
+                let matches: Vec<_> = element
+                    .children
+                    .iter()
+                    .filter_map(|v| v.as_element())
+                    .filter(|v| tag.matches(v, namespaces))
+                    .collect();
+                element = matches.get(*n).ok_or_else(|| {
+                    format!(
+                        "Cannot get node: {tag}[{n}], only {} '{tag}' nodes exist from xml path: {path}",
+                        matches.len()
+                    )
+                })?;
+            }
+            xml_path::PathPart::ChildTextMatch { tag, child, value } => {
+                element = element
+                    .children
+                    .iter()
+                    .filter_map(|v| v.as_element())
+                    .find(|v| child_text_matches(v, tag, child, value, namespaces))
+                    .ok_or_else(|| {
+                        format!(
+                            "Cannot find node: {} from xml path: {}",
+                            xml_path::Path::parts_to_string(std::slice::from_ref(part)),
+                            path
+                        )
+                    })?;
+            }
+        }
+    }
+    Ok(element)
+}
+
+/// If `element` is named `tag` and has a `child` element whose text is `value`
+fn child_text_matches(
+    element: &xmltree::Element,
+    tag: &xml_path::Name,
+    child: &xml_path::Name,
+    value: &str,
+    namespaces: &xml_path::Namespaces,
+) -> bool {
+    tag.matches(element, namespaces)
+        && element
+            .children
+            .iter()
+            .filter_map(|v| v.as_element())
+            .filter(|v| child.matches(v, namespaces))
+            .any(|v| element_text(v).unwrap_or_default() == value)
+}
+
+/// The record elements `path` selects below `xml`. the parts before the last
+/// are followed like any other path, then a last element name or `tag[child/text=value]`
+/// selects every child that matches while a last index selects just the one child
+pub fn find_records<'x>(
+    xml: &'x xmltree::Element,
+    path: &xml_path::Path,
+    namespaces: &xml_path::Namespaces,
+) -> Result<Vec<&'x xmltree::Element>, Box<dyn Error>> {
+    let Some((last, parents)) = path.parts.split_last() else {
+        Err("Paths need at least one part")?
+    };
+    find_all(xml, parents, last, path, namespaces)
+}
+
+/// Follows `parents` down from `xml`, then selects the children matching `last`
+/// the way [`find_records`] does
+fn find_all<'x>(
+    xml: &'x xmltree::Element,
+    parents: &[xml_path::PathPart],
+    last: &xml_path::PathPart,
+    path: &xml_path::Path,
+    namespaces: &xml_path::Namespaces,
+) -> Result<Vec<&'x xmltree::Element>, Box<dyn Error>> {
+    let parent = find_element(xml, parents, path, namespaces)?;
+    Ok(match last {
+        xml_path::PathPart::Element(name) => parent
+            .children
+            .iter()
+            .filter_map(|v| v.as_element())
+            .filter(|v| name.matches(v, namespaces))
+            .collect(),
+        xml_path::PathPart::ChildTextMatch { tag, child, value } => parent
+            .children
+            .iter()
+            .filter_map(|v| v.as_element())
+            .filter(|v| child_text_matches(v, tag, child, value, namespaces))
+            .collect(),
+        _ => vec![find_element(
+            parent,
+            std::slice::from_ref(last),
+            path,
+            namespaces,
+        )?],
+    })
+}
+
+/// The value of the attribute `name`, attributes are keyed by their name as written
+/// (see [`input::parse_xml`]). a prefix or `{uri}` is resolved against the namespaces
+/// in scope on the element, a prefix the config doesn't declare is matched as written.
+/// an unprefixed name falls back to a prefixed attribute with that local name
+/// (the first sorted by name) when the element has no unprefixed one
+fn find_attribute<'x>(
+    element: &'x xmltree::Element,
+    name: &xml_path::Name,
+    namespaces: &xml_path::Namespaces,
+) -> Option<&'x String> {
+    let local = name.local.as_ref();
+    let mut prefixed: Vec<_> = element
+        .attributes
+        .iter()
+        .filter_map(|(key, value)| {
+            let (prefix, key_local) = key.split_once(':')?;
+            (key_local == local).then_some((prefix, value))
+        })
+        .collect();
+    prefixed.sort();
+    let in_namespace = |uri: &str| {
+        prefixed.iter().find_map(|(prefix, value)| {
+            let scope = element.namespaces.as_ref()?;
+            (scope.get(prefix) == Some(uri)).then_some(*value)
+        })
+    };
+    match &name.namespace {
+        None => element
+            .attributes
+            .get(local)
+            .or_else(|| prefixed.first().map(|(_, value)| *value)),
+        Some(xml_path::Namespace::Uri(uri)) => in_namespace(uri),
+        Some(xml_path::Namespace::Prefix(prefix)) => match namespaces.get(prefix) {
+            Some(uri) => in_namespace(uri),
+            None => element.attributes.get(&format!("{prefix}:{local}")),
+        },
+    }
+}
+
+/// Removes the namespace declarations xmltree would otherwise write on every element
+fn strip_namespaces(element: &mut xmltree::Element) {
+    element.namespaces = None;
+    for child in &mut element.children {
+        if let xmltree::XMLNode::Element(child) = child {
+            strip_namespaces(child);
+        }
+    }
+}
+
+/// Concatenates the element's own text and CDATA nodes, `None` if it has neither
+fn element_text(element: &xmltree::Element) -> Option<Cow<'_, str>> {
+    let mut texts = element.children.iter().filter_map(|node| match node {
+        xmltree::XMLNode::Text(text) | xmltree::XMLNode::CData(text) => Some(text.as_str()),
+        _ => None,
+    });
+    let first = texts.next()?;
+    match texts.next() {
+        None => Some(Cow::Borrowed(first)),
+        Some(second) => Some(Cow::Owned(
+            [first, second].into_iter().chain(texts).collect(),
+        )),
+    }
+}
+
+fn collect_text<'l>(element: &'l xmltree::Element, texts: &mut Vec<&'l str>) {
+    for child in &element.children {
+        match child {
+            xmltree::XMLNode::Element(child) => collect_text(child, texts),
+            xmltree::XMLNode::Text(text) | xmltree::XMLNode::CData(text) => texts.push(text),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(xml: &str) -> xmltree::Element {
+        input::parse_xml(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn element_text_joins_text_and_cdata() {
+        let xml = parse("<d><![CDATA[a<b]]> tail</d>");
+        assert_eq!(element_text(&xml).unwrap(), "a<b tail");
+    }
+}
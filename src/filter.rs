@@ -0,0 +1,35 @@
+use std::error::Error;
+
+use regex::Regex;
+
+use crate::config::Condition;
+
+/// A [`Condition`] ready to be tested against values
+pub enum Matcher<'a> {
+    Equals(&'a str),
+    Contains(&'a str),
+    Regex(Regex),
+    NonEmpty,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(condition: &'a Condition<'_>) -> Result<Self, Box<dyn Error>> {
+        Ok(match condition {
+            Condition::Equals(value) => Matcher::Equals(value),
+            Condition::Contains(value) => Matcher::Contains(value),
+            Condition::Regex(regex) => Matcher::Regex(
+                Regex::new(regex).map_err(|e| format!("Invalid regex '{regex}': {e}"))?,
+            ),
+            Condition::NonEmpty => Matcher::NonEmpty,
+        })
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Equals(expected) => value == *expected,
+            Matcher::Contains(expected) => value.contains(expected),
+            Matcher::Regex(regex) => regex.is_match(value),
+            Matcher::NonEmpty => !value.is_empty(),
+        }
+    }
+}
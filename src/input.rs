@@ -0,0 +1,170 @@
+use std::{borrow::Cow, ops::Range, path::Path};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+const UTF_8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const FRAGMENTS_ROOT: &[u8] = b"xml_to_csv_fragments";
+
+/// Converts a file's bytes to UTF-8 for the parser.
+///
+/// The encoding is `forced` if given, otherwise it is taken from a byte order
+/// mark, then from the XML declaration, and defaults to UTF-8. UTF-8 input only
+/// has a leading byte order mark removed, if there is one. Anything else is
+/// decoded (invalid sequences become U+FFFD) and the declaration's encoding is
+/// rewritten to UTF-8 so the parser doesn't decode it a second time
+pub fn to_utf8<'a>(bytes: &'a [u8], forced: Option<&'static Encoding>) -> Cow<'a, [u8]> {
+    let encoding = forced
+        .or_else(|| Encoding::for_bom(bytes).map(|(encoding, _)| encoding))
+        .or_else(|| sniff_utf16(bytes))
+        .or_else(|| declared_encoding(bytes))
+        .unwrap_or(UTF_8);
+    if encoding == UTF_8 && forced.is_none() {
+        return Cow::Borrowed(bytes.strip_prefix(UTF_8_BOM).unwrap_or(bytes));
+    }
+
+    let (text, _) = encoding.decode_with_bom_removal(bytes);
+    let mut text = text.into_owned();
+    if let Some(range) = declaration_encoding(text.as_bytes()) {
+        text.replace_range(range, "UTF-8");
+    }
+    Cow::Owned(text.into_bytes())
+}
+
+/// Memory maps the file at `path`, `None` if it can't be opened or mapped
+/// (e.g. on filesystems that don't support it) so it can be read normally instead
+pub fn map_file(path: &Path) -> Option<memmap2::Mmap> {
+    let file = std::fs::File::open(path).ok()?;
+    // SAFETY: the map is only read while the file is parsed, like with any other
+    // read we assume no other process truncates the file in the meantime
+    unsafe { memmap2::Mmap::map(&file) }.ok()
+}
+
+/// Wraps UTF-8 xml fragments in a synthetic root element so they can be parsed
+/// as one document. an xml declaration at the start is dropped
+pub fn wrap_fragments(bytes: &[u8]) -> Vec<u8> {
+    let body = if bytes.starts_with(b"<?xml") {
+        find(bytes, b"?>").map_or(bytes, |end| &bytes[end + 2..])
+    } else {
+        bytes
+    };
+    let mut wrapped = Vec::with_capacity(body.len() + 2 * FRAGMENTS_ROOT.len() + 5);
+    wrapped.extend_from_slice(b"<");
+    wrapped.extend_from_slice(FRAGMENTS_ROOT);
+    wrapped.extend_from_slice(b">");
+    wrapped.extend_from_slice(body);
+    wrapped.extend_from_slice(b"</");
+    wrapped.extend_from_slice(FRAGMENTS_ROOT);
+    wrapped.extend_from_slice(b">");
+    wrapped
+}
+
+/// Parses the root element like `xmltree::Element::parse`, except prefixed attributes
+/// are keyed by their `prefix:local` name rather than only the local name so
+/// attributes that differ only in their prefix (e.g. `lang` and `xml:lang`) are both kept
+pub fn parse_xml(bytes: &[u8]) -> Result<xmltree::Element, xmltree::ParseError> {
+    use xml::reader::{EventReader, ParserConfig, XmlEvent};
+    use xmltree::{Element, ParseError, XMLNode};
+
+    let config = ParserConfig::new().ignore_comments(false);
+    let mut reader = EventReader::new_with_config(bytes, config);
+    // the open elements, innermost last
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        let event = reader.next().map_err(ParseError::MalformedXml)?;
+        let node = match event {
+            XmlEvent::StartElement {
+                name,
+                attributes,
+                namespace,
+            } => {
+                let mut element = Element::new(&name.local_name);
+                element.prefix = name.prefix;
+                element.namespace = name.namespace;
+                element.namespaces = (!namespace.is_essentially_empty()).then_some(namespace);
+                element.attributes = attributes
+                    .into_iter()
+                    .map(|attribute| match attribute.name.prefix {
+                        Some(prefix) => (
+                            format!("{prefix}:{}", attribute.name.local_name),
+                            attribute.value,
+                        ),
+                        None => (attribute.name.local_name, attribute.value),
+                    })
+                    .collect();
+                stack.push(element);
+                continue;
+            }
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().ok_or(ParseError::CannotParse)?;
+                if stack.is_empty() {
+                    return Ok(element);
+                }
+                XMLNode::Element(element)
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => XMLNode::Text(text),
+            XmlEvent::Comment(comment) => XMLNode::Comment(comment),
+            XmlEvent::ProcessingInstruction { name, data } => {
+                XMLNode::ProcessingInstruction(name, data)
+            }
+            XmlEvent::StartDocument { .. } | XmlEvent::Whitespace(_) => continue,
+            XmlEvent::EndDocument => return Err(ParseError::CannotParse),
+        };
+        // like xmltree, anything outside the root element is dropped
+        if let Some(parent) = stack.last_mut() {
+            parent.children.push(node);
+        }
+    }
+}
+
+/// Parses an `--encoding` label like `latin1`, `windows-1252` or `utf-16le`
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("Unknown encoding: '{label}'"))
+}
+
+/// UTF-16 without a byte order mark, recognized by a leading `<?`
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    match bytes.get(..4)? {
+        [b'<', 0, b'?', 0] => Some(UTF_16LE),
+        [0, b'<', 0, b'?'] => Some(UTF_16BE),
+        _ => None,
+    }
+}
+
+fn declared_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+    Encoding::for_label(&bytes[declaration_encoding(bytes)?])
+        .filter(|encoding| encoding.is_ascii_compatible())
+}
+
+/// The range of the encoding name in `<?xml ... encoding="..."?>`
+fn declaration_encoding(bytes: &[u8]) -> Option<Range<usize>> {
+    if !bytes.starts_with(b"<?xml") {
+        return None;
+    }
+    let declaration = &bytes[..find(bytes, b"?>")?];
+    let mut index = find(declaration, b"encoding")? + b"encoding".len();
+
+    let skip_whitespace = |mut index: usize| {
+        while declaration.get(index)?.is_ascii_whitespace() {
+            index += 1;
+        }
+        Some(index)
+    };
+    index = skip_whitespace(index)?;
+    if declaration[index] != b'=' {
+        return None;
+    }
+    index = skip_whitespace(index + 1)?;
+    let quote = declaration[index];
+    if quote != b'"' && quote != b'\'' {
+        return None;
+    }
+    let start = index + 1;
+    let end = start + declaration[start..].iter().position(|&c| c == quote)?;
+    Some(start..end)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,12 @@
+pub mod compute;
+pub mod config;
+pub mod extract;
+pub mod filter;
+pub mod input;
+pub mod output;
+pub mod sort;
+pub mod transform;
+pub mod xml_path;
+
+/// `src/default.json` unless another file is picked with `DEFAULT_CONFIG_PATH` when building
+pub static DEFAULT_CONFIG: &str = include_str!(env!("XML_TO_CSV_DEFAULT_CONFIG"));
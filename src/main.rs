@@ -1,44 +1,201 @@
-use std::{borrow::Cow, error::Error, path::PathBuf};
+use std::{
+    borrow::Cow,
+    error::Error,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use clap::Parser;
-use config::Config;
-
-pub mod config;
-pub mod xml_path;
-
-pub static DEFAULT_CONFIG: &str = include_str!("./default.json");
+use serde::Deserialize;
+use xml_to_csv::{
+    config::Config,
+    extract::{ReadOptions, Record, RecordError, Records},
+    input,
+    output::{HeaderCase, OutputFormat, OutputWriter, Quoting},
+    sort, transform, DEFAULT_CONFIG,
+};
 
 /// XML to CSV converter
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// path to file or folder containing XML files to extract from
-    #[arg(value_parser = verify_path_parser)]
-    xml_folder: PathBuf,
+    /// paths to folders containing XML files to extract from. rows are written
+    /// folder by folder in the order given (unless sorted with --sort-by)
+    #[arg(value_parser = verify_path_parser, num_args = 1.., required = true)]
+    xml_folders: Vec<PathBuf>,
 
-    /// Path to json config. if blank internal default will be used
+    /// Path to json config (or yaml/toml by extension). if blank internal default will be used
     #[arg(short, long, value_name = "CONFIG", value_parser = verify_path_parser)]
     config: Option<PathBuf>,
 
-    /// Path of csv file to be made
+    /// Path of csv file to be made, `-` writes to stdout
     #[arg(short, long, value_name = "OUTPUT", default_value = get_default_save_path().into_os_string())]
     save: PathBuf,
 
-    /// Log xml filepaths to stdout when parsing
+    /// Format of the output file. with ndjson duplicate titles get a `_2`, `_3`, ... suffix.
+    /// if blank it is picked from the save path extension (.tsv, .ndjson/.jsonl, else csv)
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Replace newlines in every value with TOKEN (a space if not given) and remove
+    /// all other C0 control characters (U+0000 to U+001F, tab included)
+    #[arg(long, value_name = "TOKEN", num_args = 0..=1, require_equals = true, default_missing_value = " ")]
+    sanitize: Option<String>,
+
+    /// Cut every value down to its first N chars (Unicode scalar values, not
+    /// grapheme clusters), after --sanitize
+    #[arg(long, value_name = "N")]
+    max_cell_len: Option<usize>,
+
+    /// End values cut by --max-cell-len with `…`, which counts towards N
+    #[arg(long, requires = "max_cell_len")]
+    ellipsis: bool,
+
+    /// When csv and tsv fields are quoted
+    #[arg(long, value_enum, default_value_t = Quoting::Necessary)]
+    quoting: Quoting,
+
+    /// Change the case of the titles written as the header. rows are unchanged
+    #[arg(long, value_enum, default_value_t = HeaderCase::None)]
+    header_case: HeaderCase,
+
+    /// Insert rows into a sqlite database instead of writing a save file
+    #[arg(long, value_name = "DATABASE", conflicts_with = "format")]
+    sqlite: Option<PathBuf>,
+
+    /// Table used with --sqlite. created if it doesn't exist, appended to if it does
+    /// (with --watch its rows are replaced every run)
+    #[arg(
+        long,
+        value_name = "TABLE",
+        default_value = "xml_rows",
+        requires = "sqlite"
+    )]
+    table: String,
+
+    /// Create the directories of the save path (or sqlite database) if they don't exist
+    #[arg(short = 'p', long)]
+    mkdir: bool,
+
+    /// Keep running and redo the conversion whenever files in the xml folders change
+    #[arg(short, long)]
+    watch: bool,
+
+    /// Show a progress bar with throughput and eta on stderr.
+    /// ignored when stderr isn't a terminal
+    #[arg(long)]
+    progress: bool,
+
+    /// Log xml filepaths to stderr when parsing
     #[arg(short, long)]
     log: bool,
 
-    /// skip over files that don't end with a .xml file extension
+    /// Only print errors, no warnings, logs or progress bar
+    #[arg(short, long, conflicts_with = "log")]
+    quiet: bool,
+
+    /// skip over files that don't end with a .xml file extension (implies --skip-empty)
     #[arg(short, long)]
     filter: bool,
 
+    /// skip over files that are empty or only contain whitespace
+    #[arg(long)]
+    skip_empty: bool,
+
+    /// Treat files as a list of top level elements without a single root.
+    /// every top level element is handled like its own file, producing its own row
+    /// with paths written the same as for a normal file (starting below that element)
+    #[arg(long)]
+    fragments: bool,
+
+    /// Memory map xml files instead of reading them into memory.
+    /// files that can't be mapped are read normally
+    #[arg(long)]
+    mmap: bool,
+
+    /// Try reading a file up to N more times when it fails with an I/O error.
+    /// xml that fails to parse isn't retried
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    io_retries: u32,
+
+    /// Milliseconds to wait before the first retry, doubled for every retry after it
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 100,
+        requires = "io_retries"
+    )]
+    io_retry_delay: u64,
+
+    /// Read every xml file with this encoding instead of detecting it
+    /// from a byte order mark or the xml declaration
+    #[arg(long, value_name = "ENCODING", value_parser = input::parse_encoding)]
+    encoding: Option<&'static encoding_rs::Encoding>,
+
     /// Print the default config
     #[arg(short, long, value_parser = print_default, default_value_t = false)]
     default: bool,
 
+    /// Read every file even after errors, then report all of them and write
+    /// nothing if there were any
+    #[arg(long, conflicts_with = "ignore_errors")]
+    strict: bool,
+
+    /// Write every error as a JSON object with the file `path`, failed `column`
+    /// (null if the whole file failed) and `error` message, one per line
+    #[arg(long, value_name = "PATH")]
+    error_log: Option<PathBuf>,
+
+    /// Print errors to stderr as JSON objects like the ones in --error-log rather
+    /// than as text. `path` and `column` are null for errors not about a file
+    #[arg(long)]
+    json_errors: bool,
+
+    /// Fail instead of warning when columns share a title
+    #[arg(long)]
+    strict_titles: bool,
+
     /// Continue parsing xml files if an error is encountered
     #[arg(short, long)]
     ignore_errors: bool,
+
+    /// Only write the first row for each distinct value of this column.
+    /// every distinct value seen is kept in memory until the run ends
+    #[arg(long, value_name = "COLUMN")]
+    dedup_by: Option<String>,
+
+    /// Sort rows by a column, `COLUMN:desc` for descending. ties keep their order.
+    /// every row is held in memory until all files are parsed
+    #[arg(long, value_name = "COLUMN[:desc]")]
+    sort_by: Option<sort::SortBy>,
+
+    /// Compare numbers inside values by their value when sorting (file2 before file10)
+    #[arg(long, requires = "sort_by")]
+    sort_natural: bool,
+}
+
+/// How much is written to stderr besides errors, which are always written
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    /// `--quiet`
+    Quiet,
+    /// warnings and the progress bar
+    Normal,
+    /// `--log`, also every file parsed or skipped
+    Verbose,
+}
+
+impl Args {
+    fn verbosity(&self) -> Verbosity {
+        if self.quiet {
+            Verbosity::Quiet
+        } else if self.log {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
 }
 
 fn print_default(s: &str) -> Result<bool, String> {
@@ -64,10 +221,158 @@ fn get_default_save_path() -> PathBuf {
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    run(args)
+    let result = if args.watch {
+        watch(&args)
+    } else {
+        run(&args, &AtomicBool::new(false))
+    };
+    match result {
+        Err(e) if args.json_errors => {
+            eprintln!("{}", error_json(e.as_ref()));
+            std::process::exit(1);
+        }
+        Err(e) => match e.downcast::<RecordError>() {
+            Ok(e) => Err(e.message.into()),
+            Err(e) => Err(e),
+        },
+        Ok(()) => Ok(()),
+    }
 }
 
-fn run(args: Args) -> Result<(), Box<dyn Error>> {
+fn error_json(e: &(dyn Error + 'static)) -> serde_json::Value {
+    match e.downcast_ref::<RecordError>() {
+        Some(e) => e.to_json(),
+        None => serde_json::json!({
+            "path": null,
+            "column": null,
+            "error": e.to_string(),
+        }),
+    }
+}
+
+/// Runs once, then again every time something in the xml folders changes until ctrl-c.
+/// changes are debounced so a burst of writes only causes a single rerun
+fn watch(args: &Args) -> Result<(), Box<dyn Error>> {
+    use notify::Watcher;
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
+
+    const DEBOUNCE: Duration = Duration::from_millis(500);
+    const POLL: Duration = Duration::from_millis(200);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    ctrlc::set_handler(move || handler_stop.store(true, Ordering::SeqCst))
+        .map_err(|e| format!("Failed to set ctrl-c handler: {e}"))?;
+
+    // our own output must not trigger a rerun when it's inside the watched folder
+    let outputs: Vec<_> = [
+        Some(&args.save),
+        args.sqlite.as_ref(),
+        args.error_log.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|path| std::path::absolute(path).ok())
+    .collect();
+    let is_output = |path: &Path| {
+        outputs.iter().any(|output| {
+            path.as_os_str()
+                .as_encoded_bytes()
+                .starts_with(output.as_os_str().as_encoded_bytes())
+        })
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|e| format!("Failed to create file watcher: {e}"))?;
+    for folder in &args.xml_folders {
+        let folder = std::path::absolute(folder)?;
+        watcher
+            .watch(&folder, notify::RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch '{}': {e}", folder.to_string_lossy()))?;
+    }
+
+    let relevant = |event: &notify::Result<notify::Event>| match event {
+        Ok(event) => {
+            (event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove())
+                && !event.paths.iter().all(|path| is_output(path))
+        }
+        Err(_) => false,
+    };
+
+    loop {
+        match run(args, &stop) {
+            _ if stop.load(Ordering::SeqCst) => return Ok(()),
+            Err(e) if args.json_errors => eprintln!("{}", error_json(e.as_ref())),
+            Err(e) => eprintln!("Error: {e}"),
+            Ok(()) => {}
+        }
+        if args.verbosity() >= Verbosity::Verbose {
+            eprintln!("watching: {:?}", args.xml_folders);
+        }
+
+        loop {
+            if stop.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            match receiver.recv_timeout(POLL) {
+                Ok(event) if relevant(&event) => break,
+                Ok(_) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => Err("File watcher stopped")?,
+            }
+        }
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {
+            if stop.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+type Writer = OutputWriter<std::io::BufWriter<Box<dyn Write>>>;
+
+fn open_writer(args: &Args, titles: &[Cow<'_, str>]) -> Result<Writer, Box<dyn Error>> {
+    let titles: Vec<_> = titles.iter().map(|t| args.header_case.apply(t)).collect();
+    let titles = titles.as_slice();
+    if args.mkdir {
+        let output = args.sqlite.as_ref().unwrap_or(&args.save);
+        if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                format!(
+                    "Failed to create output directory '{}': {e}",
+                    parent.to_string_lossy()
+                )
+            })?;
+        }
+    }
+    Ok(if let Some(database) = &args.sqlite {
+        OutputWriter::sqlite(database, &args.table, titles, args.watch)?
+    } else {
+        let output: Box<dyn Write> = if args.save == Path::new("-") {
+            Box::new(std::io::stdout().lock())
+        } else {
+            Box::new(std::fs::File::create(&args.save).map_err(|e| {
+                format!(
+                    "Failed to create csv file: '{}': {e}",
+                    args.save.to_string_lossy()
+                )
+            })?)
+        };
+        let format = args
+            .format
+            .unwrap_or_else(|| OutputFormat::infer(&args.save));
+        OutputWriter::new(
+            format,
+            args.quoting,
+            std::io::BufWriter::new(output),
+            titles,
+        )?
+    })
+}
+
+/// Stops with an error once `stop` is set, see [`Records::interrupt_on`]
+fn run(args: &Args, stop: &AtomicBool) -> Result<(), Box<dyn Error>> {
     let config = if let Some(path) = &args.config {
         Cow::Owned(std::fs::read_to_string(path).map_err(|e| {
             format!(
@@ -78,195 +383,284 @@ fn run(args: Args) -> Result<(), Box<dyn Error>> {
     } else {
         Cow::Borrowed(DEFAULT_CONFIG)
     };
-    let config: Config<'_> = serde_json::from_str(config.as_ref()).map_err(|e| {
+    let (parser, config) = match args
+        .config
+        .as_ref()
+        .and_then(|v| v.extension())
+        .and_then(|v| v.to_str())
+    {
+        Some("yaml" | "yml") => (
+            "YAML",
+            serde_yaml_ng::from_str(&config).map_err(|e| e.to_string()),
+        ),
+        Some("toml") => (
+            "TOML",
+            Config::deserialize(toml::Deserializer::new(&config)).map_err(|e| e.to_string()),
+        ),
+        _ => (
+            "JSON",
+            serde_json::from_str(&config).map_err(|e| e.to_string()),
+        ),
+    };
+    let config: Config<'_> = config.map_err(|e| {
         format!(
-            "Failed to parse config file '{}': {e}",
+            "Failed to parse config file '{}' as {parser}: {e}",
             args.config
                 .as_ref()
                 .and_then(|v| v.as_os_str().to_str())
                 .unwrap_or("<INTERNAL CONFIG>")
         )
     })?;
+    config.validate()?;
+    let verbosity = args.verbosity();
 
-    let csv_file = std::fs::File::create(&args.save).map_err(|e| {
-        format!(
-            "Failed to create csv file: '{}': {e}",
-            args.save.to_string_lossy()
-        )
-    })?;
-    let mut csv_writter = csv::Writer::from_writer(csv_file);
-
-    for column in &config.csv_columns {
-        csv_writter
-            .write_field(column.title.as_ref())
-            .map_err(|e| format!("Failed to write CSV field: {e}"))?;
-    }
-    csv_writter
-        .write_record(None::<&[u8]>)
-        .map_err(|e| format!("Failed to write CSV record: {e}"))?;
-
-    let dir = std::fs::read_dir(&args.xml_folder).map_err(|e| {
-        format!(
-            "Failed to read xml directory '{}': {}",
-            args.xml_folder.to_string_lossy(),
-            e
-        )
-    })?;
+    let titles = config.field_titles();
 
-    for item in dir.into_iter().flatten() {
-        if args.filter {
-            if let Some(ex) = item.path().extension() {
-                if ex != "xml" {
-                    if args.log {
-                        println!("skipping: {:?}", item.path());
-                    }
-                    continue;
-                }
-            }
+    let duplicates = config.duplicate_titles();
+    if !duplicates.is_empty() {
+        let duplicates: Vec<_> = duplicates.iter().map(|t| format!("'{t}'")).collect();
+        let message = format!("Duplicate column titles: {}", duplicates.join(", "));
+        if args.strict_titles {
+            return Err(message.into());
         }
-        if args.log {
-            println!("parsing: {:?}", item.path());
+        if verbosity >= Verbosity::Normal {
+            eprintln!("warning: {message}");
         }
+    }
 
-        let xml_file = std::fs::File::open(item.path()).map_err(|e| {
-            format!(
-                "Failed to open xml file '{}': {e}",
-                item.path().to_string_lossy()
-            )
-        })?;
-
-        let xml = xmltree::Element::parse(xml_file).map_err(|e| {
+    let dedup_index = args
+        .dedup_by
+        .as_ref()
+        .map(|title| {
+            titles
+                .iter()
+                .position(|v| v == title)
+                .ok_or_else(|| format!("Cannot dedup by '{title}', no column has that title"))
+        })
+        .transpose()?;
+    let mut seen_keys = std::collections::HashSet::new();
+
+    let sort_index = args
+        .sort_by
+        .as_ref()
+        .map(|sort_by| {
+            titles
+                .iter()
+                .position(|v| *v == sort_by.column)
+                .ok_or_else(|| {
+                    format!(
+                        "Cannot sort by '{}', no column has that title",
+                        sort_by.column
+                    )
+                })
+        })
+        .transpose()?;
+    let mut held_rows = Vec::new();
+
+    // strict runs only start writing once every file has been read without errors
+    let mut writer = if args.strict {
+        None
+    } else {
+        Some(open_writer(args, &titles)?)
+    };
+    let mut failures = Vec::new();
+
+    let mut error_log = args
+        .error_log
+        .as_ref()
+        .map(|path| {
+            std::fs::File::create(path)
+                .map(std::io::BufWriter::new)
+                .map_err(|e| {
+                    format!(
+                        "Failed to create error log '{}': {e}",
+                        path.to_string_lossy()
+                    )
+                })
+        })
+        .transpose()?;
+
+    // only drawn once the files are listed. lines written while it is drawn
+    // go through `suspend` so the bar doesn't draw over them
+    let progress =
+        indicatif::ProgressBar::hidden().with_style(indicatif::ProgressStyle::with_template(
+            "{wide_bar} {pos}/{len} files ({per_sec}, eta {eta})",
+        )?);
+
+    let mut entries = Vec::new();
+    for folder in &args.xml_folders {
+        let dir = std::fs::read_dir(folder).map_err(|e| {
             format!(
-                "Failed to parse xml file '{}': {e}",
-                item.path().to_string_lossy()
+                "Failed to read xml directory '{}': {}",
+                folder.to_string_lossy(),
+                e
             )
         })?;
+        // there's no recursion into subdirectories so they're skipped rather
+        // than failing to open them as xml files. `is_dir` follows symlinks
+        entries.extend(dir.flatten().map(|item| item.path()).filter(|path| {
+            if path.is_dir() {
+                if verbosity >= Verbosity::Verbose {
+                    progress.suspend(|| eprintln!("skipping directory: {:?}", path));
+                }
+                return false;
+            }
+            true
+        }));
+    }
 
-        let row = parse_row(&item, &xml, &config);
-
-        let row = if args.ignore_errors {
-            match row {
-                Ok(ok) => ok,
-                Err(e) => {
-                    println!(
-                        "Failed to parse xml file '{}': {e}\nskipping to next item",
-                        item.path().to_string_lossy()
-                    );
-                    continue;
+    let paths = entries
+        .into_iter()
+        .filter(|path| {
+            if args.filter {
+                if let Some(ex) = path.extension() {
+                    if ex != "xml" {
+                        if verbosity >= Verbosity::Verbose {
+                            progress.suspend(|| eprintln!("skipping: {:?}", path));
+                        }
+                        return false;
+                    }
                 }
             }
-        } else {
-            row?
-        };
+            true
+        })
+        .collect::<Vec<_>>();
 
-        csv_writter
-            .write_record(row.iter().map(|v| v.as_ref()))
-            .map_err(|e| format!("Failed to write CSV field: {e}"))?;
+    progress.set_length(paths.len() as u64);
+    if args.progress && verbosity >= Verbosity::Normal && std::io::stderr().is_terminal() {
+        progress.set_draw_target(indicatif::ProgressDrawTarget::stderr());
     }
 
-    Ok(())
-}
+    let paths = paths.into_iter().inspect(|path| {
+        progress.inc(1);
+        if verbosity >= Verbosity::Verbose {
+            progress.suspend(|| eprintln!("parsing: {:?}", path));
+        }
+    });
+
+    let options = ReadOptions {
+        encoding: args.encoding,
+        skip_empty: args.filter || args.skip_empty,
+        fragments: args.fragments,
+        mmap: args.mmap,
+        io_retries: args.io_retries,
+        io_retry_delay: std::time::Duration::from_millis(args.io_retry_delay),
+    };
 
-fn parse_row<'l>(
-    item: &std::fs::DirEntry,
-    xml: &'l xmltree::Element,
-    config: &'l Config<'_>,
-) -> Result<Vec<Cow<'l, str>>, Box<dyn Error>> {
-    let mut vals = Vec::new();
-    for column in &config.csv_columns {
-        let value = match &column.column_type {
-            config::ColumnType::ExtractXmlPath { path, default } => {
-                let res = extract_from_xml(xml, path);
-                if let Some(default) = default {
-                    res.map(Cow::Owned)
-                        .unwrap_or(Cow::Borrowed(default.as_ref()))
-                } else {
-                    Cow::Owned(res.map_err(|e| {
-                        format!(
-                            "Failed to extract column from xml file '{}': {e}",
-                            item.path().to_string_lossy()
+    for record in Records::new(&config, paths, options)?.interrupt_on(stop) {
+        let (path, row) = match record {
+            Ok(Record::Row { path, values }) => (path, values),
+            Ok(Record::Skipped { path, reason }) => {
+                if verbosity >= Verbosity::Verbose {
+                    progress.suspend(|| eprintln!("{reason}: {:?}", path));
+                }
+                continue;
+            }
+            Ok(Record::Retry {
+                path,
+                attempt,
+                error,
+            }) => {
+                if verbosity >= Verbosity::Verbose {
+                    progress.suspend(|| {
+                        eprintln!(
+                            "retrying ({attempt}/{}): {:?}: {error}",
+                            args.io_retries, path
                         )
-                    })?)
+                    });
                 }
+                continue;
             }
-            config::ColumnType::Text { text } => Cow::Borrowed(text.as_ref()),
-            config::ColumnType::Intrinsic { intrinsic } => match intrinsic {
-                config::Intrinsic::FilePath => {
-                    Cow::Owned(item.path().into_os_string().to_string_lossy().into_owned())
+            Ok(Record::Warning { message, .. }) => {
+                if verbosity >= Verbosity::Normal {
+                    progress.suspend(|| eprintln!("warning: {message}"));
                 }
-            },
+                continue;
+            }
+            Err(e) => {
+                if let Some(error_log) = &mut error_log {
+                    writeln!(error_log, "{}", e.to_json())
+                        .map_err(|e| format!("Failed to write error log: {e}"))?;
+                }
+                if args.strict {
+                    failures.push(e);
+                } else if args.ignore_errors && args.json_errors {
+                    progress.suspend(|| eprintln!("{}", e.to_json()));
+                } else if args.ignore_errors {
+                    progress.suspend(|| eprintln!("{e}\nskipping to next item"));
+                } else {
+                    return Err(e.into());
+                }
+                continue;
+            }
+        };
+        let row = match &args.sanitize {
+            Some(newline) => row
+                .iter()
+                .map(|v| transform::sanitize(v, newline))
+                .collect(),
+            None => row,
+        };
+        let row = match args.max_cell_len {
+            Some(max) => row
+                .into_iter()
+                .map(|v| transform::truncate(v, max, args.ellipsis))
+                .collect(),
+            None => row,
         };
 
-        vals.push(value);
-    }
-    Ok(vals)
-}
-
-fn extract_from_xml(
-    xml: &xmltree::Element,
-    xml_path: &xml_path::PathType,
-) -> Result<String, Box<dyn Error>> {
-    let (follow_last, path) = match xml_path {
-        xml_path::PathType::PathText(path) => (true, path),
-        xml_path::PathType::PathLen(path) => (true, path),
-        xml_path::PathType::PathAttr(path) => (false, path),
-    };
-
-    let mut element = xml;
+        if let Some(index) = dedup_index {
+            if !seen_keys.insert(row[index].clone()) {
+                if verbosity >= Verbosity::Verbose {
+                    progress.suspend(|| eprintln!("duplicate: {:?}", path));
+                }
+                continue;
+            }
+        }
 
-    let parts = if follow_last {
-        &path.parts
-    } else {
-        &path.parts[..path.parts.len() - 1]
-    };
+        match &mut writer {
+            Some(writer) if sort_index.is_none() => writer.write_row(&row)?,
+            _ => held_rows.push(row),
+        }
+    }
+    if stop.load(Ordering::SeqCst) {
+        Err("Interrupted")?
+    }
+    progress.finish();
+    if let Some(error_log) = &mut error_log {
+        error_log
+            .flush()
+            .map_err(|e| format!("Failed to write error log: {e}"))?;
+    }
 
-    for part in parts {
-        match part {
-            xml_path::PathPart::Element(node_name) => {
-                element = element.get_child(node_name.as_ref()).ok_or_else(|| {
-                    format!(
-                        "Cannot find node: {} from xml path: {}",
-                        node_name.as_ref(),
-                        path.to_string()
-                    )
-                })?;
-            }
-            xml_path::PathPart::Index(index) => {
-                element = element
-                    .children
-                    .get(*index)
-                    .ok_or_else(|| format!("Cannog get child node: {index}"))
-                    .map(|v| {
-                        v.as_element().ok_or_else(|| {
-                            format!("The item at the index: {index} is not an element")
-                        })
-                    })??;
+    if !failures.is_empty() {
+        for e in &failures {
+            if args.json_errors {
+                eprintln!("{}", e.to_json());
+            } else {
+                eprintln!("{e}");
             }
         }
+        Err(format!("{} errors, nothing was written", failures.len()))?
     }
+    let mut writer = match writer {
+        Some(writer) => writer,
+        None => open_writer(args, &titles)?,
+    };
 
-    match &xml_path {
-        xml_path::PathType::PathText(path) => Ok(element
-            .get_text()
-            .ok_or_else(|| format!("Failed to get text from {}", path.to_string()))?
-            .into_owned()),
-        xml_path::PathType::PathLen(_) => Ok(element.children.len().to_string()),
-        xml_path::PathType::PathAttr(path) => {
-            let last = path.parts.last().ok_or("Paths need at least one part")?;
-            match last {
-                xml_path::PathPart::Element(name) => {
-                    let name = name.as_ref();
-
-                    Ok(element
-                        .attributes
-                        .get(name)
-                        .ok_or_else(|| {
-                            format!("Failed to get attribute from path: {}", path.to_string())
-                        })?
-                        .to_owned())
-                }
-                xml_path::PathPart::Index(_) => Err("Cannot use an index for attributes")?,
+    if let (Some(index), Some(sort_by)) = (sort_index, &args.sort_by) {
+        held_rows.sort_by(|a, b| {
+            let (a, b) = if sort_by.descending { (b, a) } else { (a, b) };
+            if args.sort_natural {
+                sort::natural_cmp(&a[index], &b[index])
+            } else {
+                a[index].cmp(&b[index])
             }
-        }
+        });
+    }
+    for row in &held_rows {
+        writer.write_row(row)?;
     }
+
+    writer.flush()
 }
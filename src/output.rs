@@ -0,0 +1,300 @@
+use std::{borrow::Cow, error::Error, io::Write, path::Path};
+
+use clap::ValueEnum;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Comma separated values with a header row
+    Csv,
+    /// Tab separated values with a header row
+    Tsv,
+    /// One JSON object per line keyed by column title
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// Picks the format for the save path when `--format` isn't given.
+    ///
+    /// `-` (stdout) is always csv, otherwise `.tsv` is tsv, `.ndjson`/`.jsonl`
+    /// is ndjson and anything else (including `.csv`) is csv
+    pub fn infer(save: &Path) -> Self {
+        if save == Path::new("-") {
+            return Self::Csv;
+        }
+        match save.extension().and_then(|ex| ex.to_str()) {
+            Some("tsv") => Self::Tsv,
+            Some("ndjson" | "jsonl") => Self::Ndjson,
+            _ => Self::Csv,
+        }
+    }
+}
+
+/// How csv/tsv fields are quoted
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Quoting {
+    /// Only fields containing a delimiter, quote or newline
+    #[default]
+    Necessary,
+    /// Every field
+    Always,
+    /// No field. a field that would need quoting is an error rather than
+    /// being written in a way that corrupts the row
+    Never,
+    /// Every field that isn't a number
+    NonNumeric,
+}
+
+/// How titles are changed when written as the header (or ndjson keys or sqlite columns)
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HeaderCase {
+    /// As written in the config
+    #[default]
+    None,
+    Lower,
+    Upper,
+    /// Lowercase words joined with `_`. words are separated by anything that isn't
+    /// a letter or digit and by case changes, `Unit Price` and `unitPrice` are both
+    /// `unit_price` and `HTTPStatus` is `http_status`
+    Snake,
+}
+
+impl HeaderCase {
+    pub fn apply<'a>(&self, title: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::None => Cow::Borrowed(title),
+            Self::Lower => Cow::Owned(title.to_lowercase()),
+            Self::Upper => Cow::Owned(title.to_uppercase()),
+            Self::Snake => Cow::Owned(snake_case(title)),
+        }
+    }
+}
+
+fn snake_case(title: &str) -> String {
+    let chars: Vec<char> = title.chars().collect();
+    let mut snake = String::with_capacity(title.len() + 4);
+    let mut word_ended = false;
+    for (index, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            word_ended = !snake.is_empty();
+            continue;
+        }
+        let prev = index.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(index + 1);
+        // `aB` or the `S` of `PS` in `HTTPStatus` start a new word
+        let case_change = c.is_uppercase()
+            && prev.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if word_ended || (case_change && !snake.is_empty()) {
+            snake.push('_');
+        }
+        word_ended = false;
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+pub enum OutputWriter<W: Write> {
+    Csv {
+        writer: Box<csv::Writer<W>>,
+        delimiter: u8,
+        quoting: Quoting,
+    },
+    NdJson {
+        writer: W,
+        keys: Vec<String>,
+    },
+    Sqlite {
+        connection: rusqlite::Connection,
+        insert: String,
+    },
+}
+
+impl<W: Write> OutputWriter<W> {
+    /// Creates a writer for `format` and writes the header (if the format has one).
+    /// `quoting` only applies to csv and tsv
+    pub fn new(
+        format: OutputFormat,
+        quoting: Quoting,
+        writer: W,
+        titles: &[Cow<'_, str>],
+    ) -> Result<Self, Box<dyn Error>> {
+        match format {
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                let delimiter = if format == OutputFormat::Tsv {
+                    b'\t'
+                } else {
+                    b','
+                };
+                let quote_style = match quoting {
+                    Quoting::Necessary => csv::QuoteStyle::Necessary,
+                    Quoting::Always => csv::QuoteStyle::Always,
+                    Quoting::Never => csv::QuoteStyle::Never,
+                    Quoting::NonNumeric => csv::QuoteStyle::NonNumeric,
+                };
+                let csv_writter = csv::WriterBuilder::new()
+                    .delimiter(delimiter)
+                    .quote_style(quote_style)
+                    .from_writer(writer);
+                let mut writer = Self::Csv {
+                    writer: Box::new(csv_writter),
+                    delimiter,
+                    quoting,
+                };
+                writer.write_row(titles)?;
+                Ok(writer)
+            }
+            OutputFormat::Ndjson => Ok(Self::NdJson {
+                writer,
+                keys: unique_keys(titles),
+            }),
+        }
+    }
+
+    /// Opens (or creates) the database at `path` and appends into `table`,
+    /// creating it with one TEXT column per title if it doesn't exist yet.
+    /// `replace` deletes the rows already in the table instead of appending.
+    /// all rows are inserted in a single transaction committed by `flush`
+    pub fn sqlite(
+        path: &Path,
+        table: &str,
+        titles: &[Cow<'_, str>],
+        replace: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let connection = rusqlite::Connection::open(path).map_err(|e| {
+            format!(
+                "Failed to open sqlite database '{}': {e}",
+                path.to_string_lossy()
+            )
+        })?;
+
+        let table = sql_identifier(table);
+        let columns: Vec<_> = titles
+            .iter()
+            .map(|title| Cow::Owned(sql_identifier(title)))
+            .collect();
+        let columns = unique_keys(&columns);
+
+        let definitions: Vec<_> = columns.iter().map(|c| format!("\"{c}\" TEXT")).collect();
+        connection
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS \"{table}\" ({}); BEGIN;",
+                definitions.join(", ")
+            ))
+            .map_err(|e| format!("Failed to create sqlite table '{table}': {e}"))?;
+        if replace {
+            connection
+                .execute_batch(&format!("DELETE FROM \"{table}\";"))
+                .map_err(|e| format!("Failed to clear sqlite table '{table}': {e}"))?;
+        }
+
+        let names: Vec<_> = columns.iter().map(|c| format!("\"{c}\"")).collect();
+        let params: Vec<_> = (1..=columns.len()).map(|i| format!("?{i}")).collect();
+        let insert = format!(
+            "INSERT INTO \"{table}\" ({}) VALUES ({})",
+            names.join(", "),
+            params.join(", ")
+        );
+        Ok(Self::Sqlite { connection, insert })
+    }
+
+    pub fn write_row<S: AsRef<str>>(&mut self, row: &[S]) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Csv {
+                writer,
+                delimiter,
+                quoting,
+            } => {
+                if *quoting == Quoting::Never {
+                    let needs_quotes = |field: &str| {
+                        field
+                            .bytes()
+                            .any(|b| b == *delimiter || matches!(b, b'"' | b'\n' | b'\r'))
+                    };
+                    if let Some(field) = row.iter().find(|v| needs_quotes(v.as_ref())) {
+                        Err(format!(
+                            "Cannot write CSV field {:?} without quotes, it contains a delimiter, quote or newline",
+                            field.as_ref()
+                        ))?
+                    }
+                }
+                writer
+                    .write_record(row.iter().map(|v| v.as_ref()))
+                    .map_err(|e| format!("Failed to write CSV field: {e}"))?
+            }
+            Self::NdJson { writer, keys } => {
+                let object: serde_json::Map<String, serde_json::Value> = keys
+                    .iter()
+                    .zip(row)
+                    .map(|(key, value)| (key.clone(), value.as_ref().into()))
+                    .collect();
+                serde_json::to_writer(&mut *writer, &object)
+                    .map_err(|e| format!("Failed to write JSON record: {e}"))?;
+                writer
+                    .write_all(b"\n")
+                    .map_err(|e| format!("Failed to write JSON record: {e}"))?;
+            }
+            Self::Sqlite { connection, insert } => {
+                connection
+                    .prepare_cached(insert)
+                    .and_then(|mut statement| {
+                        statement
+                            .execute(rusqlite::params_from_iter(row.iter().map(|v| v.as_ref())))
+                    })
+                    .map_err(|e| format!("Failed to insert sqlite row: {e}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Csv { writer, .. } => writer.flush(),
+            Self::NdJson { writer, .. } => writer.flush(),
+            Self::Sqlite { connection, .. } => {
+                connection
+                    .execute_batch("COMMIT;")
+                    .map_err(|e| format!("Failed to commit sqlite transaction: {e}"))?;
+                return Ok(());
+            }
+        }
+        .map_err(|e| format!("Failed to flush output: {e}"))?;
+        Ok(())
+    }
+}
+
+/// Makes column titles usable as JSON object keys.
+///
+/// The first occurrence of a title is kept as is, every later duplicate gets
+/// the smallest suffix `_2`, `_3`, ... that doesn't collide with another title or key.
+/// e.g. `["a", "a", "a_2"]` becomes `["a", "a_3", "a_2"]`
+pub fn unique_keys(titles: &[Cow<'_, str>]) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::with_capacity(titles.len());
+    for title in titles {
+        let mut key = title.to_string();
+        let mut suffix = 2;
+        while keys.contains(&key) || (key != *title && titles.iter().any(|t| *t == key)) {
+            key = format!("{title}_{suffix}");
+            suffix += 1;
+        }
+        keys.push(key);
+    }
+    keys
+}
+
+/// Turns a title into a valid SQL identifier.
+///
+/// Every character that isn't ASCII alphanumeric or `_` is replaced with `_`
+/// and a leading `_` is added if it would otherwise be empty or start with a digit
+pub fn sql_identifier(title: &str) -> String {
+    let mut ident: String = title
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if ident.chars().next().is_none_or(|c| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    ident
+}
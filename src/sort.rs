@@ -0,0 +1,59 @@
+use std::{cmp::Ordering, str::FromStr};
+
+/// `COLUMN` or `COLUMN:desc` (`COLUMN:asc` is also accepted)
+#[derive(Clone, Debug)]
+pub struct SortBy {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, descending) = match s.rsplit_once(':') {
+            Some((column, "desc")) => (column, true),
+            Some((column, "asc")) => (column, false),
+            _ => (s, false),
+        };
+        if column.is_empty() {
+            return Err("Sort column cannot be empty".into());
+        }
+        Ok(SortBy {
+            column: column.to_owned(),
+            descending,
+        })
+    }
+}
+
+/// Compares runs of digits by their numeric value and everything else as text,
+/// so `file2` sorts before `file10`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a;
+    let mut b = b;
+    loop {
+        let (Some(ac), Some(bc)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if ac.is_ascii_digit() && bc.is_ascii_digit() {
+            let (a_num, a_rest) = split_digits(a);
+            let (b_num, b_rest) = split_digits(b);
+            a = a_rest;
+            b = b_rest;
+            let a_num = a_num.trim_start_matches('0');
+            let b_num = b_num.trim_start_matches('0');
+            a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num))
+        } else {
+            a = &a[ac.len_utf8()..];
+            b = &b[bc.len_utf8()..];
+            ac.cmp(&bc)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()))
+}
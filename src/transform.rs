@@ -0,0 +1,200 @@
+use std::{error::Error, fmt::Write};
+
+use base64::Engine;
+use chrono::{format::StrftimeItems, DateTime, NaiveDate, NaiveDateTime};
+
+use crate::config::{Encoding, OnNoMatch, Reformat, ReformatKind, RegexCapture, Transform};
+
+/// Applies a single step of a column's `transforms`
+pub fn apply(value: String, transform: &Transform<'_>) -> Result<String, Box<dyn Error>> {
+    match transform {
+        Transform::Trim => Ok(value.trim().to_owned()),
+        Transform::CollapseWhitespace => Ok(collapse_whitespace(&value)),
+        Transform::Decode { encoding, lossy } => decode(&value, encoding, *lossy),
+        Transform::Regex(capture) => regex_capture(value, capture),
+        Transform::Reformat(reformat) => self::reformat(value, reformat),
+        Transform::Truncate { max, ellipsis } => Ok(truncate(value, *max, *ellipsis)),
+    }
+}
+
+/// Decodes an extracted value, failing on invalid UTF-8 unless `lossy` is set
+pub fn decode(value: &str, encoding: &Encoding, lossy: bool) -> Result<String, Box<dyn Error>> {
+    let bytes = match encoding {
+        Encoding::Base64 => base64::engine::general_purpose::STANDARD
+            .decode(value.trim())
+            .map_err(|e| format!("Failed to decode base64 value: {e}"))?,
+        Encoding::Hex => {
+            hex::decode(value.trim()).map_err(|e| format!("Failed to decode hex value: {e}"))?
+        }
+    };
+    if lossy {
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    } else {
+        Ok(String::from_utf8(bytes)
+            .map_err(|e| format!("Decoded value is not valid UTF-8: {e}"))?)
+    }
+}
+
+/// Replaces every run of whitespace with a single space
+pub fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Replaces each `\r\n`, `\r` and `\n` with `newline` and removes every other
+/// C0 control character (U+0000 to U+001F, tab included)
+pub fn sanitize(value: &str, newline: &str) -> String {
+    let mut sanitized = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                sanitized.push_str(newline);
+            }
+            '\n' => sanitized.push_str(newline),
+            '\u{0}'..='\u{1f}' => {}
+            c => sanitized.push(c),
+        }
+    }
+    sanitized
+}
+
+/// Cuts `value` down to its first `max` chars. chars are Unicode scalar values, not
+/// grapheme clusters, so e.g. a combining accent can be cut off the letter it belongs to.
+/// with `ellipsis` the last char kept is replaced by `…` if anything was cut
+pub fn truncate(mut value: String, max: usize, ellipsis: bool) -> String {
+    let Some((end, _)) = value.char_indices().nth(max) else {
+        return value;
+    };
+    if ellipsis && max > 0 {
+        let end = value[..end].char_indices().last().map_or(0, |(i, _)| i);
+        value.truncate(end);
+        value.push('…');
+    } else {
+        value.truncate(end);
+    }
+    value
+}
+
+/// Prefixes every `\` and every occurrence of a separator with a `\`
+pub fn escape(value: &str, separators: &[&str]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let mut rest = value;
+    'outer: while let Some(c) = rest.chars().next() {
+        for separator in separators.iter().filter(|s| !s.is_empty()) {
+            if let Some(after) = rest.strip_prefix(separator) {
+                escaped.push('\\');
+                escaped.push_str(separator);
+                rest = after;
+                continue 'outer;
+            }
+        }
+        if c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    escaped
+}
+
+/// Replaces `value` with the group `capture` captures from it, see [`RegexCapture`]
+pub fn regex_capture(value: String, capture: &RegexCapture) -> Result<String, Box<dyn Error>> {
+    let regex = &capture.pattern.0;
+    let group = capture
+        .group
+        .unwrap_or(if regex.captures_len() > 1 { 1 } else { 0 });
+    match regex.captures(&value) {
+        Some(captures) => Ok(captures.get(group).map_or("", |m| m.as_str()).to_owned()),
+        None => match capture.on_no_match {
+            OnNoMatch::Default => Err(format!("Regex '{regex}' doesn't match {value:?}"))?,
+            OnNoMatch::Passthrough => Ok(value),
+            OnNoMatch::Empty => Ok(String::new()),
+        },
+    }
+}
+
+pub fn reformat(value: String, reformat: &Reformat<'_>) -> Result<String, Box<dyn Error>> {
+    let res = match &reformat.kind {
+        ReformatKind::Date { from, to } => reformat_date(&value, from, to.as_deref()),
+        ReformatKind::Number {
+            decimals,
+            thousands,
+        } => reformat_number(&value, *decimals, thousands.as_deref()),
+    };
+    match res {
+        Err(_) if reformat.passthrough => Ok(value),
+        res => res,
+    }
+}
+
+fn reformat_date(value: &str, from: &str, to: Option<&str>) -> Result<String, Box<dyn Error>> {
+    let value = value.trim();
+    let mut formatted = String::new();
+    let res = if let Ok(date) = DateTime::parse_from_str(value, from) {
+        match to {
+            Some(to) => write!(
+                formatted,
+                "{}",
+                date.format_with_items(StrftimeItems::new(to))
+            ),
+            None => write!(formatted, "{}", date.to_rfc3339()),
+        }
+    } else if let Ok(date) = NaiveDateTime::parse_from_str(value, from) {
+        let to = to.unwrap_or("%Y-%m-%dT%H:%M:%S");
+        write!(
+            formatted,
+            "{}",
+            date.format_with_items(StrftimeItems::new(to))
+        )
+    } else if let Ok(date) = NaiveDate::parse_from_str(value, from) {
+        let to = to.unwrap_or("%Y-%m-%d");
+        write!(
+            formatted,
+            "{}",
+            date.format_with_items(StrftimeItems::new(to))
+        )
+    } else {
+        Err(format!(
+            "Failed to parse '{value}' as a date with format '{from}'"
+        ))?
+    };
+    res.map_err(|_| format!("Invalid date format '{}'", to.unwrap_or_default()))?;
+    Ok(formatted)
+}
+
+fn reformat_number(
+    value: &str,
+    decimals: Option<usize>,
+    thousands: Option<&str>,
+) -> Result<String, Box<dyn Error>> {
+    let number: f64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Failed to parse '{value}' as a number"))?;
+    let formatted = match decimals {
+        Some(decimals) => format!("{number:.decimals$}"),
+        None => number.to_string(),
+    };
+    let Some(thousands) = thousands else {
+        return Ok(formatted);
+    };
+
+    let (sign, unsigned) = match formatted.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", formatted.as_str()),
+    };
+    let (integer, fraction) = match unsigned.find('.') {
+        Some(dot) => unsigned.split_at(dot),
+        None => (unsigned, ""),
+    };
+    let mut grouped = String::from(sign);
+    for (index, digit) in integer.chars().enumerate() {
+        if index != 0 && (integer.len() - index) % 3 == 0 {
+            grouped.push_str(thousands);
+        }
+        grouped.push(digit);
+    }
+    grouped.push_str(fraction);
+    Ok(grouped)
+}
@@ -1,4 +1,4 @@
-use std::{borrow::Cow, str::FromStr};
+use std::{borrow::Cow, collections::BTreeMap, str::FromStr};
 
 use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
 
@@ -7,35 +7,126 @@ pub enum PathType<'l> {
     #[serde(borrow = "'l")]
     #[serde(rename = "path_text")]
     PathText(Path<'l>),
+    /// The text of the element and all of its descendants in document order,
+    /// CDATA sections included
+    #[serde(borrow = "'l")]
+    #[serde(rename = "path_text_deep")]
+    PathTextDeep(Path<'l>),
     #[serde(borrow = "'l")]
     #[serde(rename = "path_len")]
     PathLen(Path<'l>),
+    /// How many children of the element the parts before the last lead to match
+    /// the last part, e.g. `order.line` counts the `line` children of `order`.
+    /// there are no wildcard parts, every part before the last follows the first
+    /// match like any other path so only the children of that one parent are counted.
+    /// a last `tag[child/text=value]` counts every match, a last index or `tag[n]` is 0 or 1
+    #[serde(borrow = "'l")]
+    #[serde(rename = "path_match_count")]
+    PathMatchCount(Path<'l>),
+    /// The attribute named by the last part, or the nth attribute sorted
+    /// by name when the last part is an index. a `prefix:` or `{uri}` on the
+    /// name is resolved like it is for elements, e.g. `xml:lang`
     #[serde(borrow = "'l")]
     #[serde(rename = "path_attr")]
     PathAttr(Path<'l>),
+    /// Every attribute of the element as `name=value` pairs sorted by name.
+    /// `\`, and the separators when they appear in a name or value, are escaped with a `\`
+    #[serde(borrow = "'l")]
+    #[serde(rename = "path_all_attrs")]
+    PathAllAttrs(Path<'l>),
+    /// The element itself serialized as XML, on a single line without namespace
+    /// declarations unless the `pretty` or `include_namespaces` options are set
+    #[serde(borrow = "'l")]
+    #[serde(rename = "path_xml")]
+    PathXml(Path<'l>),
+    /// The content of the element's first comment child.
+    /// comments outside the root element can't be reached
+    #[serde(borrow = "'l")]
+    #[serde(rename = "path_comment")]
+    PathComment(Path<'l>),
+    /// The content of the element's first processing instruction child with `target`,
+    /// empty if it has none. instructions outside the root element can't be reached
+    #[serde(rename = "path_pi")]
+    PathPI {
+        #[serde(borrow = "'l")]
+        path: Path<'l>,
+        #[serde(borrow = "'l")]
+        target: Cow<'l, str>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(transparent)]
+impl<'l> PathType<'l> {
+    pub fn path(&self) -> &Path<'l> {
+        match self {
+            PathType::PathText(path)
+            | PathType::PathTextDeep(path)
+            | PathType::PathLen(path)
+            | PathType::PathMatchCount(path)
+            | PathType::PathAttr(path)
+            | PathType::PathAllAttrs(path)
+            | PathType::PathXml(path)
+            | PathType::PathComment(path)
+            | PathType::PathPI { path, .. } => path,
+        }
+    }
+
+    /// Checks for paths that can never extract anything
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let parts = &self.path().parts;
+        let attribute_allowed =
+            |index| matches!(self, PathType::PathAttr(_)) && index + 1 == parts.len();
+        for (index, part) in parts.iter().enumerate() {
+            if matches!(part, PathPart::Attribute(_)) && !attribute_allowed(index) {
+                return Err("Can only use `@` for the last part of `path_attr`");
+            }
+        }
+        match self {
+            PathType::PathAttr(path) => match path.parts.last() {
+                Some(PathPart::Element(_) | PathPart::Attribute(_) | PathPart::Index(_)) => Ok(()),
+                Some(PathPart::NthOfType { .. } | PathPart::ChildTextMatch { .. }) => {
+                    Err("Cannot use `[...]` for attributes")
+                }
+                None => Err("Paths need at least one part"),
+            },
+            PathType::PathMatchCount(path) if path.parts.is_empty() => {
+                Err("Paths need at least one part")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parts separated by dots, followed from the element rows are read from (a record
+/// of `record_path`, otherwise the root element). a leading `/` makes the path
+/// absolute so it is always followed from the root element, e.g. `/header.id`.
+/// `.` is a path without parts, the element itself (`/.` is the root element)
+#[derive(Debug)]
 pub struct Path<'l> {
-    #[serde(serialize_with = "ser")]
-    #[serde(deserialize_with = "de")]
-    #[serde(borrow = "'l")]
+    pub absolute: bool,
     pub parts: Vec<PathPart<'l>>,
 }
 
 impl<'l> Path<'l> {
-    fn parts_to_string(parts: &[PathPart<'_>]) -> String {
+    pub fn parts_to_string(parts: &[PathPart<'_>]) -> String {
         let mut string = String::new();
 
         for (index, part) in parts.iter().enumerate() {
             match part {
-                PathPart::Element(str) => {
-                    string.push_str(str);
+                PathPart::Element(name) => {
+                    string.push_str(&name.to_string());
+                }
+                PathPart::Attribute(name) => {
+                    string.push_str(&format!("@{}", name));
                 }
                 PathPart::Index(index) => {
                     string.push_str(&format!("{}", index));
                 }
+                PathPart::NthOfType { tag, n } => {
+                    string.push_str(&format!("{}[{}]", tag, n));
+                }
+                PathPart::ChildTextMatch { tag, child, value } => {
+                    string.push_str(&format!("{}[{}/text={}]", tag, child, quote(value)));
+                }
             }
 
             if parts.len() - 1 != index {
@@ -45,58 +136,139 @@ impl<'l> Path<'l> {
 
         string
     }
-}
-
-fn ser<S>(parts: &[PathPart<'_>], s: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    s.serialize_str(&Path::parts_to_string(parts))
-}
 
-fn de<'l, D>(d: D) -> Result<Vec<PathPart<'l>>, D::Error>
-where
-    D: Deserializer<'l>,
-{
-    struct StrVisitor;
-    impl<'de> Visitor<'de> for StrVisitor {
-        type Value = Vec<PathPart<'de>>;
-
-        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-            formatter.write_str("A path 'example.foo.1.bar.5'")
+    /// Checks a path used as `record_path`, which selects elements so it needs
+    /// at least one part and can't have `@` parts
+    pub fn validate_records(&self) -> Result<(), &'static str> {
+        if self.parts.is_empty() {
+            return Err("Paths need at least one part");
         }
-
-        fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
-        where
-            E: serde::de::Error,
+        if self
+            .parts
+            .iter()
+            .any(|part| matches!(part, PathPart::Attribute(_)))
         {
-            Path::try_from(v)
-                .map_err(|e| serde::de::Error::custom(e))
-                .map(|v| v.parts)
+            return Err("Cannot use `@` in `record_path`");
         }
+        Ok(())
+    }
 
-        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-        where
-            E: serde::de::Error,
-        {
-            self.visit_string(v.to_owned())
+    /// Splits a path on the dots that aren't inside a `{uri}`, a `'quoted'` name or `[...]`
+    fn split_parts(s: &str) -> Vec<&str> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let mut in_uri = false;
+        let mut in_quotes = false;
+        let mut in_brackets = false;
+        for (index, c) in s.char_indices() {
+            match c {
+                '{' if !in_quotes => in_uri = true,
+                '}' if !in_quotes => in_uri = false,
+                '\'' if !in_uri => in_quotes = !in_quotes,
+                '[' if !in_uri && !in_quotes => in_brackets = true,
+                ']' if !in_uri && !in_quotes => in_brackets = false,
+                '.' if !in_uri && !in_quotes && !in_brackets => {
+                    parts.push(&s[start..index]);
+                    start = index + 1;
+                }
+                _ => {}
+            }
         }
+        parts.push(&s[start..]);
+        parts
+    }
 
-        fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
-        where
-            E: serde::de::Error,
-        {
-            Path::try_from(v)
-                .map_err(|e| serde::de::Error::custom(e))
-                .map(|v| v.parts)
+    /// Parses a single part between dots. `tag[n]` is [`PathPart::NthOfType`],
+    /// `tag[child/text=value]` is [`PathPart::ChildTextMatch`], a plain number
+    /// is [`PathPart::Index`], `@name` is [`PathPart::Attribute`] and anything else
+    /// is [`PathPart::Element`]
+    fn parse_part(part: &'l str) -> Result<PathPart<'l>, PathParseError> {
+        if let Ok(index) = usize::from_str(part) {
+            return Ok(PathPart::Index(index));
+        }
+        if let Some(name) = part.strip_prefix('@') {
+            return Ok(PathPart::Attribute(Name::parse(name)?));
+        }
+        let Some((tag, inner)) = split_brackets(part)? else {
+            return Ok(PathPart::Element(Name::parse(part)?));
+        };
+        let tag = Name::parse(tag)?;
+        if let Ok(n) = usize::from_str(inner) {
+            return Ok(PathPart::NthOfType { tag, n });
+        }
+        let (child, value) = split_unquoted(inner, '/').ok_or(PathParseError::InvalidPredicate)?;
+        let value = value
+            .strip_prefix("text=")
+            .ok_or(PathParseError::InvalidPredicate)?;
+        Ok(PathPart::ChildTextMatch {
+            tag,
+            child: Name::parse(child)?,
+            value: unquote(value)?,
+        })
+    }
+
+    pub fn into_owned(self) -> Path<'static> {
+        Path {
+            absolute: self.absolute,
+            parts: self.parts.into_iter().map(PathPart::into_owned).collect(),
         }
     }
-    d.deserialize_str(StrVisitor)
+}
+
+impl Serialize for Path<'_> {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de: 'l, 'l> Deserialize<'de> for Path<'l> {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        d.deserialize_str(StrVisitor)
+    }
+}
+
+struct StrVisitor;
+impl<'de> Visitor<'de> for StrVisitor {
+    type Value = Path<'de>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("A path 'example.foo.1.bar.5'")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Path::try_from(v).map_err(|e| serde::de::Error::custom(e))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_string(v.to_owned())
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Path::try_from(v).map_err(|e| serde::de::Error::custom(e))
+    }
 }
 
 #[derive(Debug)]
 pub enum PathParseError {
     EmptyPart,
+    UnclosedNamespace,
+    UnclosedQuote,
+    InvalidPredicate,
 }
 
 impl std::fmt::Display for PathParseError {
@@ -109,16 +281,20 @@ impl<'l> TryFrom<&'l str> for Path<'l> {
     type Error = PathParseError;
 
     fn try_from(s: &'l str) -> Result<Self, Self::Error> {
+        let (absolute, s) = match s.strip_prefix('/') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
         let mut parts = Vec::new();
+        if s == "." {
+            return Ok(Self { absolute, parts });
+        }
 
-        for part in s.split('.') {
-            let part = usize::from_str(part)
-                .map(PathPart::Index)
-                .unwrap_or(PathPart::Element(Cow::Borrowed(part)));
-            parts.push(part);
+        for part in Path::split_parts(s) {
+            parts.push(Path::parse_part(part)?);
         }
 
-        Ok(Self { parts })
+        Ok(Self { absolute, parts })
     }
 }
 
@@ -126,27 +302,207 @@ impl TryFrom<String> for Path<'_> {
     type Error = PathParseError;
 
     fn try_from(s: String) -> Result<Self, Self::Error> {
-        let mut parts = Vec::new();
-
-        for part in s.split('.') {
-            let part = usize::from_str(part)
-                .map(PathPart::Index)
-                .unwrap_or(PathPart::Element(Cow::Owned(part.to_owned())));
-            parts.push(part);
-        }
-
-        Ok(Self { parts })
+        Path::try_from(s.as_str()).map(Path::into_owned)
     }
 }
 
-impl<'l> ToString for Path<'l> {
-    fn to_string(&self) -> String {
-        Path::parts_to_string(&self.parts)
+impl<'l> std::fmt::Display for Path<'l> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.absolute {
+            f.write_str("/")?;
+        }
+        if self.parts.is_empty() {
+            return f.write_str(".");
+        }
+        f.write_str(&Path::parts_to_string(&self.parts))
     }
 }
 
 #[derive(Debug)]
 pub enum PathPart<'l> {
-    Element(Cow<'l, str>),
+    Element(Name<'l>),
+    /// `@name`, only allowed as the last part of a `path_attr` path where
+    /// it's the same as `name`, e.g. `@id` is the `id` attribute of the element itself
+    Attribute(Name<'l>),
     Index(usize),
+    /// The nth (zero based) child element named `tag`, written `tag[n]`
+    NthOfType {
+        tag: Name<'l>,
+        n: usize,
+    },
+    /// The first child element named `tag` (in document order) that has a `child`
+    /// element whose text is exactly `value`, written `tag[child/text=value]`.
+    /// `value` can be `'quoted'` to contain `[`, `]`, `{` or `}`
+    ChildTextMatch {
+        tag: Name<'l>,
+        child: Name<'l>,
+        value: Cow<'l, str>,
+    },
+}
+
+impl PathPart<'_> {
+    pub fn into_owned(self) -> PathPart<'static> {
+        match self {
+            PathPart::Element(name) => PathPart::Element(name.into_owned()),
+            PathPart::Attribute(name) => PathPart::Attribute(name.into_owned()),
+            PathPart::Index(index) => PathPart::Index(index),
+            PathPart::NthOfType { tag, n } => PathPart::NthOfType {
+                tag: tag.into_owned(),
+                n,
+            },
+            PathPart::ChildTextMatch { tag, child, value } => PathPart::ChildTextMatch {
+                tag: tag.into_owned(),
+                child: child.into_owned(),
+                value: Cow::Owned(value.into_owned()),
+            },
+        }
+    }
+}
+
+/// Namespace prefix to namespace URI, declared at the top level of the config
+pub type Namespaces<'l> = BTreeMap<Cow<'l, str>, Cow<'l, str>>;
+
+/// An element or attribute name, written `local`, `prefix:local` or `{uri}local`.
+///
+/// A name without a namespace matches on the local name alone. A prefix is
+/// resolved to a URI with the config's `namespaces` so the document may use any
+/// prefix for that URI, if the config doesn't declare the prefix it is instead
+/// compared with the prefix written in the document.
+#[derive(Debug)]
+pub struct Name<'l> {
+    pub namespace: Option<Namespace<'l>>,
+    pub local: Cow<'l, str>,
+}
+
+#[derive(Debug)]
+pub enum Namespace<'l> {
+    Prefix(Cow<'l, str>),
+    Uri(Cow<'l, str>),
+}
+
+impl<'l> Name<'l> {
+    /// The local name may be `'quoted'` so it can contain characters that are
+    /// part of the path syntax, a `'` inside the quotes is written `''`
+    fn parse(s: &'l str) -> Result<Self, PathParseError> {
+        if let Some(rest) = s.strip_prefix('{') {
+            let (uri, local) = rest
+                .split_once('}')
+                .ok_or(PathParseError::UnclosedNamespace)?;
+            return Ok(Name {
+                namespace: Some(Namespace::Uri(Cow::Borrowed(uri))),
+                local: unquote(local)?,
+            });
+        }
+        let prefixed = if s.starts_with('\'') {
+            None
+        } else {
+            s.split_once(':')
+        };
+        Ok(match prefixed {
+            Some((prefix, local)) => Name {
+                namespace: Some(Namespace::Prefix(Cow::Borrowed(prefix))),
+                local: unquote(local)?,
+            },
+            None => Name {
+                namespace: None,
+                local: unquote(s)?,
+            },
+        })
+    }
+
+    pub fn matches(&self, element: &xmltree::Element, namespaces: &Namespaces<'_>) -> bool {
+        if element.name != self.local.as_ref() {
+            return false;
+        }
+        match &self.namespace {
+            None => true,
+            Some(Namespace::Uri(uri)) => element.namespace.as_deref() == Some(uri.as_ref()),
+            Some(Namespace::Prefix(prefix)) => match namespaces.get(prefix) {
+                Some(uri) => element.namespace.as_deref() == Some(uri.as_ref()),
+                None => element.prefix.as_deref() == Some(prefix.as_ref()),
+            },
+        }
+    }
+
+    pub fn into_owned(self) -> Name<'static> {
+        Name {
+            namespace: self.namespace.map(|namespace| match namespace {
+                Namespace::Prefix(prefix) => Namespace::Prefix(Cow::Owned(prefix.into_owned())),
+                Namespace::Uri(uri) => Namespace::Uri(Cow::Owned(uri.into_owned())),
+            }),
+            local: Cow::Owned(self.local.into_owned()),
+        }
+    }
+}
+
+impl std::fmt::Display for Name<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let local = quote(&self.local);
+        match &self.namespace {
+            None => write!(f, "{}", local),
+            Some(Namespace::Prefix(prefix)) => write!(f, "{}:{}", prefix, local),
+            Some(Namespace::Uri(uri)) => write!(f, "{{{}}}{}", uri, local),
+        }
+    }
+}
+
+/// Removes the quotes around a `'quoted'` name, `''` inside them is a literal `'`
+fn unquote(s: &str) -> Result<Cow<'_, str>, PathParseError> {
+    let Some(inner) = s.strip_prefix('\'') else {
+        return Ok(Cow::Borrowed(s));
+    };
+    let inner = inner
+        .strip_suffix('\'')
+        .ok_or(PathParseError::UnclosedQuote)?;
+    if inner.replace("''", "").contains('\'') {
+        return Err(PathParseError::UnclosedQuote);
+    }
+    Ok(if inner.contains('\'') {
+        Cow::Owned(inner.replace("''", "'"))
+    } else {
+        Cow::Borrowed(inner)
+    })
+}
+
+/// Quotes a name if it would otherwise be read as something else
+fn quote(name: &str) -> Cow<'_, str> {
+    let plain = !name.is_empty()
+        && !name.bytes().all(|b| b.is_ascii_digit())
+        && !name.starts_with('@')
+        && !name.contains(['\'', '.', '[', ']', '=', '/', '{', '}', ':']);
+    if plain {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(format!("'{}'", name.replace('\'', "''")))
+    }
+}
+
+/// Splits `tag[inner]` at the first `[` that isn't inside a `{uri}` or quotes,
+/// `None` if the part has no brackets
+fn split_brackets(part: &str) -> Result<Option<(&str, &str)>, PathParseError> {
+    let Some((tag, rest)) = split_unquoted(part, '[') else {
+        return Ok(None);
+    };
+    let inner = rest
+        .strip_suffix(']')
+        .ok_or(PathParseError::InvalidPredicate)?;
+    Ok(Some((tag, inner)))
+}
+
+/// Splits at the first `delimiter` that isn't inside a `{uri}` or quotes
+fn split_unquoted(s: &str, delimiter: char) -> Option<(&str, &str)> {
+    let mut in_uri = false;
+    let mut in_quotes = false;
+    for (index, c) in s.char_indices() {
+        match c {
+            '{' if !in_quotes => in_uri = true,
+            '}' if !in_quotes => in_uri = false,
+            '\'' if !in_uri => in_quotes = !in_quotes,
+            c if c == delimiter && !in_uri && !in_quotes => {
+                return Some((&s[..index], &s[index + c.len_utf8()..]))
+            }
+            _ => {}
+        }
+    }
+    None
 }
@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+
+use xml_to_csv::{
+    config::{ColumnType, Config},
+    extract,
+    xml_path::{PathPart, PathType},
+    DEFAULT_CONFIG,
+};
+
+#[test]
+fn yaml_config_with_path_text() {
+    let yaml = "
+version: 1
+csv_columns:
+  - title: Disk
+    path_text: device.platformname
+  - title: Second
+    path_text: 'results.item[1]'
+";
+    let config: Config = serde_yaml_ng::from_str(yaml).unwrap();
+    config.validate().unwrap();
+
+    let paths: Vec<_> = config
+        .csv_columns
+        .iter()
+        .map(|column| match &column.column_type {
+            ColumnType::ExtractXmlPath {
+                path: PathType::PathText(path),
+                ..
+            } => path.to_string(),
+            other => panic!("expected path_text, got {other:?}"),
+        })
+        .collect();
+    assert_eq!(paths, ["device.platformname", "results.item[1]"]);
+
+    let ColumnType::ExtractXmlPath {
+        path: PathType::PathText(path),
+        ..
+    } = &config.csv_columns[1].column_type
+    else {
+        unreachable!()
+    };
+    assert!(matches!(path.parts[1], PathPart::NthOfType { n: 1, .. }));
+}
+
+#[test]
+fn default_config_is_valid() {
+    let config: Config = serde_json::from_str(DEFAULT_CONFIG).unwrap();
+    config.validate().unwrap();
+    assert!(!config.csv_columns.is_empty());
+}
+
+#[test]
+fn column_errors_keep_their_location() {
+    let e = serde_json::from_str::<Config>(r#"[{"title":"a","path_txt":"x"}]"#).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "data did not match any variant of untagged enum ColumnType at line 1 column 29"
+    );
+
+    let e = serde_json::from_str::<Config>(
+        r#"{"version": 1, "csv_columns": [{"title": "a", "path_text": 1}]}"#,
+    )
+    .unwrap_err();
+    assert_eq!((e.line(), e.column()), (1, 61));
+}
+
+#[test]
+fn config_must_be_an_array_or_a_map() {
+    let e = serde_json::from_str::<Config>(r#""columns""#).unwrap_err();
+    assert!(e
+        .to_string()
+        .contains("expected An array of columns or a map with `csv_columns`"));
+}
+
+#[test]
+fn newer_versions_ask_for_an_upgrade_before_reading_columns() {
+    let e = serde_json::from_str::<Config>(
+        r#"{"version": 2, "csv_columns": [{"title": "x", "path_regex": "a"}]}"#,
+    )
+    .unwrap_err();
+    assert!(
+        e.to_string()
+            .starts_with("Config version 2 is newer than the supported version 1"),
+        "{e}"
+    );
+}
+
+#[test]
+fn columns_may_come_before_the_version() {
+    let config: Config = serde_json::from_str(
+        r#"{"csv_columns": [{"title": "x", "path_text": "a"}], "version": 1}"#,
+    )
+    .unwrap();
+    config.validate().unwrap();
+    assert_eq!(config.csv_columns[0].title(), "x");
+
+    let e = serde_json::from_str::<Config>(
+        r#"{"csv_columns": [{"title": "x", "path_regex": "a"}], "version": 2}"#,
+    )
+    .unwrap_err();
+    assert!(
+        e.to_string().starts_with("Config version 2 is newer"),
+        "{e}"
+    );
+}
+
+#[test]
+fn cyclic_column_references_are_rejected() {
+    let config: Config = serde_json::from_str(
+        r#"[
+            {"title": "a", "expr": "b + 1"},
+            {"title": "b", "condition": {"column": "a", "operator": "non_empty"}, "then": "1"}
+        ]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.validate().unwrap_err(),
+        "Cyclic column references: 'a' -> 'b' -> 'a'"
+    );
+}
+
+#[test]
+fn when_is_evaluated_after_the_computed_column_it_reads() {
+    let config: Config = serde_json::from_str(
+        r#"[
+            {"title": "id", "path_text": "id"},
+            {"title": "big", "condition": {"column": "double", "operator": "equals", "value": "2"},
+                "then": "yes", "else": "no"},
+            {"title": "double", "expr": "id * 2"}
+        ]"#,
+    )
+    .unwrap();
+    config.validate().unwrap();
+    assert_eq!(config.compute_order().unwrap(), [2, 1]);
+
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/record.xml");
+    let rows: Vec<_> = extract::rows(&config, std::iter::once(file))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(rows, [["1", "yes", "2"]]);
+}
+
+#[test]
+fn record_paths_must_select_elements() {
+    for (record_path, error) in [
+        (".", "Invalid record path: Paths need at least one part"),
+        (
+            "a.@b",
+            "Invalid record path: Cannot use `@` in `record_path`",
+        ),
+    ] {
+        let json = format!(
+            r#"{{"record_path": "{record_path}", "csv_columns": [{{"title": "id", "path_text": "id"}}]}}"#
+        );
+        let config: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.validate().unwrap_err(), error);
+    }
+
+    let config: Config = serde_json::from_str(
+        r#"{"record_path": "items.item", "csv_columns": [{"title": "id", "path_text": "id"}]}"#,
+    )
+    .unwrap();
+    config.validate().unwrap();
+}
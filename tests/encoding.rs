@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use xml_to_csv::{config::Config, extract, input};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Checks `name` becomes valid UTF-8 containing `expected`, and that `expected` is
+/// what gets extracted from its `text` element
+fn extract_text(name: &str, expected: &str) {
+    let bytes = std::fs::read(fixture(name)).unwrap();
+    let utf8 = input::to_utf8(&bytes, None);
+    let utf8 = std::str::from_utf8(&utf8).unwrap();
+    assert!(
+        utf8.contains(expected),
+        "{utf8:?} doesn't contain {expected:?}"
+    );
+
+    let config: Config =
+        serde_json::from_str(r#"[{"title": "text", "path_text": "text"}]"#).unwrap();
+    let rows: Vec<_> = extract::rows(&config, std::iter::once(fixture(name)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(rows, [[expected]]);
+}
+
+#[test]
+fn latin1_with_declaration() {
+    extract_text("latin1.xml", "café");
+}
+
+#[test]
+fn utf16le_with_bom() {
+    extract_text("utf16le_bom.xml", "ü16");
+}
+
+#[test]
+fn utf16be_without_bom() {
+    extract_text("utf16be_no_bom.xml", "ü16 be");
+}
+
+#[test]
+fn declaration_is_rewritten_to_utf8() {
+    let bytes = std::fs::read(fixture("latin1.xml")).unwrap();
+    let utf8 = input::to_utf8(&bytes, None);
+    assert!(utf8.starts_with(br#"<?xml version="1.0" encoding="UTF-8"?>"#));
+}
+
+#[test]
+fn utf8_bom_is_stripped() {
+    let bytes = std::fs::read(fixture("utf8_bom.xml")).unwrap();
+    assert!(bytes.starts_with(b"\xEF\xBB\xBF"));
+    let utf8 = input::to_utf8(&bytes, None);
+    assert!(utf8.starts_with(b"<?xml"));
+
+    extract_text("utf8_bom.xml", "bom");
+}
+
+#[test]
+fn utf8_without_bom_is_unchanged() {
+    let bytes = std::fs::read(fixture("record.xml")).unwrap();
+    assert_eq!(*input::to_utf8(&bytes, None), *bytes);
+}
@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use xml_to_csv::{
+    config::ExtractOptions,
+    extract::{self, Scope},
+    input,
+    xml_path::{Namespaces, PathType},
+};
+
+/// The value `path` extracts from the root element of `xml`
+fn extract(
+    xml: &[u8],
+    path: PathType,
+    options: &ExtractOptions,
+    namespaces: &Namespaces,
+) -> String {
+    let root = input::parse_xml(xml).unwrap();
+    path.validate().unwrap();
+    extract::extract_from_xml(Scope::root(&root), &path, options, namespaces).unwrap()
+}
+
+const ITEM: &[u8] = br#"<doc xmlns:m="urn:m"><item id="1"><m:name>one</m:name><tags><tag>a</tag></tags></item></doc>"#;
+
+#[test]
+fn path_xml_is_compact_by_default() {
+    let path = PathType::PathXml("item".try_into().unwrap());
+    assert_eq!(
+        extract(ITEM, path, &ExtractOptions::default(), &Namespaces::new()),
+        r#"<item id="1"><m:name>one</m:name><tags><tag>a</tag></tags></item>"#
+    );
+}
+
+#[test]
+fn path_xml_with_namespaces_and_indent() {
+    let options = ExtractOptions {
+        pretty: true,
+        include_namespaces: true,
+        ..Default::default()
+    };
+    let path = PathType::PathXml("item".try_into().unwrap());
+    assert_eq!(
+        extract(ITEM, path, &options, &Namespaces::new()),
+        r#"<item xmlns:m="urn:m" id="1">
+  <m:name>one</m:name>
+  <tags>
+    <tag>a</tag>
+  </tags>
+</item>"#
+    );
+}
+
+/// The value of `path_attr` in the `xml_lang.xml` fixture
+fn xml_lang_attr(path: &str, namespaces: &Namespaces) -> String {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/xml_lang.xml");
+    let path = PathType::PathAttr(path.try_into().unwrap());
+    extract(
+        &std::fs::read(file).unwrap(),
+        path,
+        &ExtractOptions::default(),
+        namespaces,
+    )
+}
+
+#[test]
+fn prefixed_attributes_are_found() {
+    let namespaces = Namespaces::new();
+    assert_eq!(xml_lang_attr("title.@xml:lang", &namespaces), "en");
+    assert_eq!(xml_lang_attr("title.@lang", &namespaces), "plain");
+    assert_eq!(
+        xml_lang_attr("link.@xlink:href", &namespaces),
+        "https://example.com/"
+    );
+}
+
+#[test]
+fn attribute_prefixes_resolve_through_the_config() {
+    let namespaces = Namespaces::from([("l".into(), "http://www.w3.org/1999/xlink".into())]);
+    assert_eq!(
+        xml_lang_attr("link.@l:href", &namespaces),
+        "https://example.com/"
+    );
+}
+
+const ROOT: &[u8] = br#"<doc id="7">text<child/>more</doc>"#;
+
+#[test]
+fn root_attribute() {
+    for path in ["@id", "/@id"] {
+        let path = PathType::PathAttr(path.try_into().unwrap());
+        assert_eq!(
+            extract(ROOT, path, &ExtractOptions::default(), &Namespaces::new()),
+            "7"
+        );
+    }
+}
+
+#[test]
+fn root_text() {
+    for path in [".", "/."] {
+        let path = PathType::PathText(path.try_into().unwrap());
+        assert_eq!(
+            extract(ROOT, path, &ExtractOptions::default(), &Namespaces::new()),
+            "textmore"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use std::{
+    borrow::Cow,
+    io::Sink,
+    path::{Path, PathBuf},
+};
+
+use xml_to_csv::output::OutputWriter;
+
+/// A database path in the target directory, removed if a previous run left it behind
+fn database(name: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn insert(path: &Path, rows: &[&str], replace: bool) {
+    let titles = [Cow::Borrowed("id")];
+    let mut writer = OutputWriter::<Sink>::sqlite(path, "rows", &titles, replace).unwrap();
+    for row in rows {
+        writer.write_row(&[row]).unwrap();
+    }
+    writer.flush().unwrap();
+}
+
+fn ids(path: &Path) -> Vec<String> {
+    let connection = rusqlite::Connection::open(path).unwrap();
+    let mut statement = connection.prepare("SELECT id FROM rows").unwrap();
+    statement
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[test]
+fn sqlite_appends_by_default() {
+    let path = database("append.sqlite");
+    insert(&path, &["1", "2"], false);
+    insert(&path, &["3"], false);
+    assert_eq!(ids(&path), ["1", "2", "3"]);
+}
+
+#[test]
+fn sqlite_replace_clears_the_table() {
+    let path = database("replace.sqlite");
+    insert(&path, &["1", "2"], true);
+    insert(&path, &["3"], true);
+    assert_eq!(ids(&path), ["3"]);
+}
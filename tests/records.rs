@@ -0,0 +1,208 @@
+use std::{
+    cell::Cell,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use xml_to_csv::{
+    config::Config,
+    extract::{self, ReadOptions, Record, Records},
+};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+#[test]
+fn rows_are_read_lazily() {
+    let config: Config = serde_json::from_str(r#"[{"title": "id", "path_text": "id"}]"#).unwrap();
+    let opened = Cell::new(0);
+    let paths = std::iter::repeat_with(|| {
+        opened.set(opened.get() + 1);
+        fixture("record.xml")
+    })
+    .take(1000);
+
+    let rows = extract::rows(&config, paths).unwrap();
+    assert_eq!(rows.take(3).map(Result::unwrap).count(), 3);
+    assert_eq!(opened.get(), 3);
+}
+
+#[test]
+fn rows_counts_every_file() {
+    let config: Config = serde_json::from_str(r#"[{"title": "id", "path_text": "id"}]"#).unwrap();
+    let paths = std::iter::repeat_n(fixture("record.xml"), 50);
+
+    let mut count = 0;
+    for row in extract::rows(&config, paths).unwrap() {
+        assert_eq!(row.unwrap(), ["1"]);
+        count += 1;
+    }
+    assert_eq!(count, 50);
+}
+
+#[test]
+fn rows_validates_the_config() {
+    let config: Config = serde_json::from_str(
+        r#"[{"title": "id", "path_text": "missing", "default": {"expr": "1+1"}}]"#,
+    )
+    .unwrap();
+    let paths = std::iter::once(fixture("record.xml"));
+
+    let Err(e) = extract::rows(&config, paths) else {
+        panic!("a computed default should be rejected");
+    };
+    assert!(e
+        .to_string()
+        .contains("Cannot use a computed column as a `default`"));
+}
+
+/// The values of every row `Records` reads from `paths`
+fn read_rows(config: &Config, paths: &[PathBuf], options: ReadOptions) -> Vec<Vec<String>> {
+    Records::new(config, paths.iter().cloned(), options)
+        .unwrap()
+        .filter_map(|record| match record.unwrap() {
+            Record::Row { values, .. } => Some(values),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn fragments_are_rows_of_their_own() {
+    let config: Config = serde_json::from_str(r#"[{"title": "id", "path_text": "id"}]"#).unwrap();
+    let options = ReadOptions {
+        fragments: true,
+        ..Default::default()
+    };
+
+    let rows = read_rows(&config, &[fixture("fragments.xml")], options);
+    assert_eq!(rows, [["1"], ["2"], ["3"]]);
+}
+
+#[test]
+fn mmap_reads_the_same_rows() {
+    let config: Config = serde_json::from_str(r#"[{"title": "id", "path_text": "id"}]"#).unwrap();
+    let paths = [fixture("record.xml"), fixture("fragments.xml")];
+
+    let read = read_rows(
+        &config,
+        &paths,
+        ReadOptions {
+            fragments: true,
+            ..Default::default()
+        },
+    );
+    let mapped = read_rows(
+        &config,
+        &paths,
+        ReadOptions {
+            fragments: true,
+            mmap: true,
+            ..Default::default()
+        },
+    );
+    assert_eq!(read, [["1"], ["1"], ["2"], ["3"]]);
+    assert_eq!(read, mapped);
+}
+
+#[test]
+fn truncated_expand_columns_warn_before_the_row() {
+    let config: Config = serde_json::from_str(
+        r#"[{"title": "tag", "path_text": "tag", "max": 2, "warn_truncated": true}]"#,
+    )
+    .unwrap();
+    let paths = std::iter::once(fixture("tags.xml"));
+
+    let records: Vec<_> = Records::new(&config, paths, ReadOptions::default())
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    let [Record::Warning { message, .. }, Record::Row { values, .. }] = records.as_slice() else {
+        panic!("expected a warning and a row, got {records:?}");
+    };
+    assert!(message.contains("matched 3 elements"), "{message}");
+    assert_eq!(values, &["a", "b"]);
+}
+
+#[test]
+fn retries_are_returned_before_waiting() {
+    let config: Config = serde_json::from_str(r#"[{"title": "id", "path_text": "id"}]"#).unwrap();
+    let paths = std::iter::once(fixture("missing.xml"));
+    let options = ReadOptions {
+        io_retries: 2,
+        io_retry_delay: Duration::from_millis(200),
+        ..Default::default()
+    };
+    let mut records = Records::new(&config, paths, options).unwrap();
+
+    let start = Instant::now();
+    let Some(Ok(Record::Retry { attempt: 1, .. })) = records.next() else {
+        panic!("expected the first retry");
+    };
+    assert!(start.elapsed() < Duration::from_millis(200));
+    let Some(Ok(Record::Retry { attempt: 2, .. })) = records.next() else {
+        panic!("expected the second retry");
+    };
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert!(records.next().unwrap().is_err());
+    assert!(start.elapsed() >= Duration::from_millis(600));
+    assert!(records.next().is_none());
+}
+
+#[test]
+fn columns_validate_the_config() {
+    let config: Config = serde_json::from_str(
+        r#"[{"title": "a", "path_text": "missing", "default": {"expr": "1+1"}}]"#,
+    )
+    .unwrap();
+
+    let Err(e) = extract::Columns::new(&config) else {
+        panic!("a computed default should be rejected");
+    };
+    assert!(e
+        .to_string()
+        .contains("Cannot use a computed column as a `default`"));
+}
+
+#[test]
+fn interrupting_cuts_a_retry_short() {
+    let config: Config = serde_json::from_str(r#"[{"title": "id", "path_text": "id"}]"#).unwrap();
+    let paths = std::iter::once(fixture("missing.xml"));
+    let options = ReadOptions {
+        io_retries: 1,
+        io_retry_delay: Duration::from_secs(10),
+        ..Default::default()
+    };
+    let stop = AtomicBool::new(false);
+    let mut records = Records::new(&config, paths, options)
+        .unwrap()
+        .interrupt_on(&stop);
+
+    let Some(Ok(Record::Retry { .. })) = records.next() else {
+        panic!("expected a retry");
+    };
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(Duration::from_millis(100));
+            stop.store(true, Ordering::SeqCst);
+        });
+        assert!(records.next().is_none());
+    });
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn cdata_is_part_of_the_text() {
+    let config: Config =
+        serde_json::from_str(r#"[{"title": "text", "path_text": "text"}]"#).unwrap();
+    let rows: Vec<_> = extract::rows(&config, std::iter::once(fixture("cdata.xml")))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(rows, [[r#"a<b & "c" tail"#]]);
+}
@@ -0,0 +1,61 @@
+//! Timing of reading many files, run with `cargo test --release -- --ignored --nocapture`
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use xml_to_csv::{
+    config::Config,
+    extract::{self, Columns, ReadOptions, Record, Records, Run, Scope},
+    input,
+};
+
+const CONFIG: &str = r#"[
+    {"title": "id", "path_text": "id"},
+    {"title": "name", "path_text": "name"},
+    {"title": "double", "expr": "id * 2"},
+    {"title": "numeric", "condition": {"column": "id", "operator": "regex", "value": "^[0-9]+$"},
+        "then": "yes", "else": "no"}
+]"#;
+
+const FILES: usize = 20_000;
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+#[test]
+#[ignore]
+fn records_against_reading_every_file_fresh() {
+    let config: Config = serde_json::from_str(CONFIG).unwrap();
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/record.xml");
+    let paths = || std::iter::repeat_n(file.clone(), FILES);
+
+    // a new buffer, parsed config and compiled regex for every file
+    let fresh = time(|| {
+        let run = Run::new(&config).unwrap();
+        for path in paths() {
+            let bytes = std::fs::read(&path).unwrap();
+            let root = input::parse_xml(&input::to_utf8(&bytes, None)).unwrap();
+            let columns = Columns::new(&config).unwrap();
+            let row =
+                extract::parse_row(&path, Scope::root(&root), &columns, &run, &mut Vec::new())
+                    .unwrap();
+            assert_eq!(row, ["1", "one", "2", "yes"]);
+        }
+    });
+    // one buffer and one set of resolved columns for the whole run
+    let records = time(|| {
+        for record in Records::new(&config, paths(), ReadOptions::default()).unwrap() {
+            let Record::Row { values, .. } = record.unwrap() else {
+                panic!("expected only rows");
+            };
+            assert_eq!(values, ["1", "one", "2", "yes"]);
+        }
+    });
+
+    println!("{FILES} files: read fresh {fresh:?}, through Records {records:?}");
+}
@@ -0,0 +1,82 @@
+use xml_to_csv::{
+    config::{RegexCapture, Transform},
+    transform,
+};
+
+fn capture(json: &str) -> RegexCapture {
+    serde_json::from_str(json).unwrap()
+}
+
+/// `id-(\d+)` with the given `on_no_match`
+fn with_on_no_match(on_no_match: &str) -> RegexCapture {
+    capture(&format!(
+        r#"{{"pattern": "id-(\\d+)", "on_no_match": "{on_no_match}"}}"#
+    ))
+}
+
+#[test]
+fn regex_capture_takes_the_first_group() {
+    let capture = capture(r#"{"pattern": "id-(\\d+)"}"#);
+    let value = transform::regex_capture("id-42".to_owned(), &capture).unwrap();
+    assert_eq!(value, "42");
+}
+
+#[test]
+fn regex_capture_passes_unmatched_values_through_by_default() {
+    let capture = capture(r#"{"pattern": "id-(\\d+)"}"#);
+    let value = transform::regex_capture("none".to_owned(), &capture).unwrap();
+    assert_eq!(value, "none");
+
+    let capture = with_on_no_match("passthrough");
+    let value = transform::regex_capture("none".to_owned(), &capture).unwrap();
+    assert_eq!(value, "none");
+}
+
+#[test]
+fn regex_capture_empties_unmatched_values() {
+    let capture = with_on_no_match("empty");
+    let value = transform::regex_capture("none".to_owned(), &capture).unwrap();
+    assert_eq!(value, "");
+}
+
+#[test]
+fn regex_capture_fails_unmatched_values_for_the_default() {
+    let capture = with_on_no_match("default");
+    let e = transform::regex_capture("none".to_owned(), &capture).unwrap_err();
+    assert_eq!(e.to_string(), r#"Regex 'id-(\d+)' doesn't match "none""#);
+}
+
+#[test]
+fn transforms_apply_in_order() {
+    let transforms: Vec<Transform> = serde_json::from_str(
+        r#"[
+            "trim",
+            {"regex": {"pattern": "^name: (.*)$"}},
+            {"truncate": {"max": 6, "ellipsis": true}}
+        ]"#,
+    )
+    .unwrap();
+
+    let value = transforms
+        .iter()
+        .try_fold("  name: Bartholomew \n".to_owned(), transform::apply)
+        .unwrap();
+    assert_eq!(value, "Barth…");
+}
+
+#[test]
+fn transform_order_matters() {
+    let transforms: Vec<Transform> = serde_json::from_str(
+        r#"[
+            {"regex": {"pattern": "^name: (.*)$", "on_no_match": "empty"}},
+            "trim"
+        ]"#,
+    )
+    .unwrap();
+
+    let value = transforms
+        .iter()
+        .try_fold("  name: Bart".to_owned(), transform::apply)
+        .unwrap();
+    assert_eq!(value, "");
+}
@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+
+use xml_to_csv::xml_path::{Name, Path, PathPart};
+
+fn name(local: &str) -> Name<'_> {
+    Name {
+        namespace: None,
+        local: Cow::Borrowed(local),
+    }
+}
+
+/// Writes a path to `element` and parses it back
+fn round_trip(element: &str) -> (String, Path<'static>) {
+    let path = Path {
+        absolute: false,
+        parts: vec![PathPart::Element(name(element))],
+    };
+    let written = path.to_string();
+    let parsed = Path::try_from(written.clone()).unwrap();
+    (written, parsed)
+}
+
+#[test]
+fn special_characters_in_names_round_trip() {
+    for name in [
+        "a[b", "a]b", "a.b", "a=b", "a'b", "a/b", "a{b", "a}b", "a:b", "@a", "12", "",
+    ] {
+        let (written, parsed) = round_trip(name);
+        assert!(written.starts_with('\''), "{name:?} was written {written}");
+        let [PathPart::Element(parsed)] = parsed.parts.as_slice() else {
+            panic!("{written} parsed as {parsed:?}");
+        };
+        assert!(parsed.namespace.is_none(), "{written} parsed as {parsed:?}");
+        assert_eq!(parsed.local, name, "{written} didn't round trip");
+    }
+}
+
+#[test]
+fn plain_names_are_not_quoted() {
+    assert_eq!(round_trip("item").0, "item");
+    assert_eq!(round_trip("item-2_b").0, "item-2_b");
+}
+
+#[test]
+fn quoted_names_in_longer_paths() {
+    let path = Path::try_from("root.'a.b'[1].'x=y'").unwrap();
+    assert_eq!(path.to_string(), "root.'a.b'[1].'x=y'");
+    let [_, PathPart::NthOfType { tag, n: 1 }, PathPart::Element(last)] = path.parts.as_slice()
+    else {
+        panic!("unexpected parts {:?}", path.parts);
+    };
+    assert_eq!(tag.local, "a.b");
+    assert_eq!(last.local, "x=y");
+}
+
+#[test]
+fn special_characters_in_text_matches_round_trip() {
+    for value in ["a[b", "a]b", "a'b", "a{b", "a}b", "{uri}b", "a.b", ""] {
+        let path = Path {
+            absolute: false,
+            parts: vec![PathPart::ChildTextMatch {
+                tag: name("item"),
+                child: name("id"),
+                value: Cow::Borrowed(value),
+            }],
+        };
+        let written = path.to_string();
+        let parsed = Path::try_from(written.as_str()).unwrap();
+        let [PathPart::ChildTextMatch { value: parsed, .. }] = parsed.parts.as_slice() else {
+            panic!("{written} parsed as {parsed:?}");
+        };
+        assert_eq!(parsed, value, "{written} didn't round trip");
+    }
+}